@@ -1,22 +1,93 @@
-use crate::token::Token;
+use logos::Span;
+use crate::token::{Spanned, Token};
+
+pub type SpannedExpr = Spanned<Expr>;
+pub type SpannedPattern = Spanned<Pattern>;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expr {
     Identifier(String),
     Number(f64),
+    Integer(i64),
+    #[cfg(feature = "bigint")]
+    BigInt(num_bigint::BigInt),
+    #[cfg(feature = "decimal")]
+    Decimal(rust_decimal::Decimal),
     StringLiteral(String),
     Boolean(bool),
     Nil,
-    List(Vec<Expr>),
-    Map(Vec<(Expr, Expr)>),
-    Call(Box<Expr>, Vec<Expr>),
-    Assign(Box<Expr>, Box<Expr>),
-    Block(Vec<Expr>),
-    If(Box<Expr>, Box<Expr>, Option<Box<Expr>>),
-    While(Box<Expr>, Box<Expr>),
-    Property(Box<Expr>, String),
-    Binary(Box<Expr>, Token, Box<Expr>),
-    Unary(Token, Box<Expr>),
-    Lambda(Vec<String>, Box<Expr>),
-    Match(Box<Expr>, Vec<(Expr, Expr)>),
-}
\ No newline at end of file
+    List(Vec<SpannedExpr>),
+    Map(Vec<(SpannedExpr, SpannedExpr)>),
+    Call(Box<SpannedExpr>, Vec<SpannedExpr>),
+    /// `value |> name(args)`, which calls `name(value, args)`, or the method `value.name(args)` when no
+    /// variable `name` is defined.
+    Pipeline(Box<SpannedExpr>, Vec<SpannedExpr>),
+    Assign(Box<SpannedExpr>, Box<SpannedExpr>),
+    /// `name = value`, which updates the innermost existing variable `name` rather than defining one.
+    Reassign(String, Box<SpannedExpr>),
+    Block(Vec<SpannedExpr>),
+    If(Box<SpannedExpr>, Box<SpannedExpr>, Option<Box<SpannedExpr>>),
+    While(Box<SpannedExpr>, Box<SpannedExpr>),
+    /// `for (iterable) |params| body`, with the parameters bound to each item in turn.
+    For(Vec<String>, Box<SpannedExpr>, Box<SpannedExpr>),
+    Break(Option<Box<SpannedExpr>>),
+    Continue,
+    Return(Option<Box<SpannedExpr>>),
+    Property(Box<SpannedExpr>, String),
+    Index(Box<SpannedExpr>, Box<SpannedExpr>),
+    /// `target[from..to]`, where either bound may be left out.
+    Slice(Box<SpannedExpr>, Option<Box<SpannedExpr>>, Option<Box<SpannedExpr>>),
+    Binary(Box<SpannedExpr>, Token, Box<SpannedExpr>),
+    /// `start..end` or `start..=end` when inclusive, optionally followed by `by step`.
+    Range(Box<SpannedExpr>, Box<SpannedExpr>, bool, Option<Box<SpannedExpr>>),
+    Unary(Token, Box<SpannedExpr>),
+    Lambda(Vec<String>, Box<SpannedExpr>),
+    Match(Box<SpannedExpr>, Vec<MatchArm>, Option<Box<SpannedExpr>>),
+    Interpolate(Vec<Part>),
+    Error,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Part {
+    Text(String),
+    Expr(SpannedExpr, Option<FormatSpec>),
+}
+
+/// A `{value:spec}` format spec: `[[fill]align][0][width][.precision]`, with align one of `<`, `^`, `>`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FormatSpec {
+    pub fill: char,
+    pub align: Option<char>,
+    pub zero: bool,
+    pub width: Option<usize>,
+    pub precision: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Binding(String),
+    Number(f64),
+    Integer(i64),
+    StringLiteral(String),
+    Boolean(bool),
+    Nil,
+    /// Element patterns, plus the pattern bound to the remaining elements after `..`.
+    List(Vec<SpannedPattern>, Option<Box<SpannedPattern>>),
+    Map(Vec<(String, SpannedPattern)>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchArm {
+    pub pattern: SpannedPattern,
+    pub guard: Option<SpannedExpr>,
+    pub body: SpannedExpr,
+}
+
+/// A `##` doc comment attached to the definition that follows it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Doc {
+    pub name: String,
+    pub text: String,
+    pub span: Span,
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use crate::error::{Error, Result};
+use crate::eval;
+use crate::obj::{BuiltinFn, Obj, ObjRef};
+use crate::token::Token;
+
+/// The native functions every script starts with in its global scope.
+pub fn globals() -> HashMap<String, ObjRef> {
+    let builtins: &[(&'static str, BuiltinFn)] = &[
+        ("int", int),
+        ("float", float),
+        ("num", num),
+        ("_add", _add),
+        ("_sub", _sub),
+        ("_mul", _mul),
+        ("_div", _div),
+        ("_mod", _mod),
+        ("_eq", _eq),
+        ("_ne", _ne),
+        ("_lt", _lt),
+        ("_gt", _gt),
+        ("_le", _le),
+        ("_ge", _ge),
+        ("_and", _and),
+        ("_or", _or),
+        #[cfg(feature = "bigint")]
+        ("bigint", bigint),
+        #[cfg(feature = "decimal")]
+        ("decimal", decimal),
+    ];
+
+    builtins
+        .iter()
+        .map(|&(name, func)| (name.to_string(), Obj::Builtin { name, func }.as_ref()))
+        .collect()
+}
+
+fn arg(name: &str, args: &[ObjRef]) -> Result<ObjRef> {
+    match args {
+        [arg] => Ok(arg.clone()),
+        _ => Err(Error::runtime(format!("{} expects 1 argument, got {}", name, args.len()))),
+    }
+}
+
+/// Defines a builtin per binary operator, so `_add(a, b)` behaves like `a + b`.
+macro_rules! operators {
+    ($($name:ident => $op:ident),* $(,)?) => {
+        $(
+            fn $name(args: &[ObjRef]) -> Result<ObjRef> {
+                operator(stringify!($name), Token::$op, args)
+            }
+        )*
+    };
+}
+
+operators! {
+    _add => Plus,
+    _sub => Minus,
+    _mul => Star,
+    _div => Slash,
+    _mod => Percent,
+    _eq => Equality,
+    _ne => NotEqual,
+    _lt => LessThan,
+    _gt => GreaterThan,
+    _le => LessThanEqual,
+    _ge => GreaterThanEqual,
+    _and => And,
+    _or => Or,
+}
+
+/// Applies `op` to two arguments. `and` and `or` evaluate both, then yield the deciding one like the
+/// operators do.
+fn operator(name: &str, op: Token, args: &[ObjRef]) -> Result<ObjRef> {
+    let [left, right] = args else {
+        return Err(Error::runtime(format!("{} expects 2 arguments, got {}", name, args.len())));
+    };
+    match op {
+        Token::And | Token::Or => {
+            let decided = left.borrow().is_truthy() == (op == Token::Or);
+            Ok(if decided { left.clone() } else { right.clone() })
+        }
+        op => Ok(eval::binary(&left.borrow(), &op, &right.borrow())?.as_ref()),
+    }
+}
+
+fn convert_error(name: &str, obj: &Obj) -> Error {
+    Error::runtime(format!("Cannot convert {} to {}", obj, name))
+}
+
+/// Converts to `Int`, truncating floats and decimals towards zero.
+fn int(args: &[ObjRef]) -> Result<ObjRef> {
+    let arg = arg("int", args)?;
+    let obj = &*arg.borrow();
+    let n = match obj {
+        Obj::Int(n) => Some(*n),
+        Obj::Number(n) if n.is_finite() && n.trunc() >= i64::MIN as f64 && n.trunc() < i64::MAX as f64 => Some(*n as i64),
+        Obj::String(s) => s.trim().parse().ok(),
+        #[cfg(feature = "bigint")]
+        Obj::BigInt(n) => num_traits::ToPrimitive::to_i64(n),
+        #[cfg(feature = "decimal")]
+        Obj::Decimal(n) => num_traits::ToPrimitive::to_i64(n),
+        _ => None,
+    };
+    n.map(|n| Obj::Int(n).as_ref()).ok_or_else(|| convert_error("int", obj))
+}
+
+/// Converts to a floating point `Number`, which may lose precision.
+fn float(args: &[ObjRef]) -> Result<ObjRef> {
+    let arg = arg("float", args)?;
+    let obj = &*arg.borrow();
+    let n = match obj {
+        Obj::String(s) => s.trim().parse().ok(),
+        #[cfg(feature = "decimal")]
+        Obj::Decimal(n) => num_traits::ToPrimitive::to_f64(n),
+        obj => obj.as_number(),
+    };
+    n.map(|n| Obj::Number(n).as_ref()).ok_or_else(|| convert_error("float", obj))
+}
+
+/// Converts a string to an `Int`, or to a `Number` if it isn't a whole number. Numbers are returned
+/// as they are.
+fn num(args: &[ObjRef]) -> Result<ObjRef> {
+    let arg = arg("num", args)?;
+    let obj = &*arg.borrow();
+    match obj {
+        Obj::String(s) => {
+            let s = s.trim();
+            let n = s.parse().map(Obj::Int).or_else(|_| s.parse().map(Obj::Number));
+            n.map(|n| n.as_ref()).map_err(|_| convert_error("num", obj))
+        }
+        obj if obj.is_numeric() => Ok(arg.clone()),
+        obj => Err(convert_error("num", obj)),
+    }
+}
+
+/// Converts to an arbitrary-precision `BigInt`, truncating floats and decimals towards zero.
+#[cfg(feature = "bigint")]
+fn bigint(args: &[ObjRef]) -> Result<ObjRef> {
+    use num_bigint::BigInt;
+
+    let arg = arg("bigint", args)?;
+    let obj = &*arg.borrow();
+    let n = match obj {
+        Obj::Int(n) => Some(BigInt::from(*n)),
+        Obj::BigInt(n) => Some(n.clone()),
+        Obj::Number(n) => num_traits::FromPrimitive::from_f64(n.trunc()),
+        Obj::String(s) => s.trim().parse().ok(),
+        #[cfg(feature = "decimal")]
+        Obj::Decimal(n) => n.trunc().to_string().parse().ok(),
+        _ => None,
+    };
+    n.map(|n| Obj::BigInt(n).as_ref()).ok_or_else(|| convert_error("bigint", obj))
+}
+
+/// Converts to an exact `Decimal`. Strings keep their written scale, so `decimal('1.10')` prints `1.10`.
+#[cfg(feature = "decimal")]
+fn decimal(args: &[ObjRef]) -> Result<ObjRef> {
+    use rust_decimal::Decimal;
+
+    let arg = arg("decimal", args)?;
+    let obj = &*arg.borrow();
+    let n = match obj {
+        Obj::Int(n) => Some(Decimal::from(*n)),
+        Obj::Decimal(n) => Some(*n),
+        Obj::Number(n) => Decimal::from_f64_retain(*n),
+        Obj::String(s) => s.trim().parse().ok(),
+        #[cfg(feature = "bigint")]
+        Obj::BigInt(n) => n.to_string().parse().ok(),
+        _ => None,
+    };
+    n.map(|n| Obj::Decimal(n).as_ref()).ok_or_else(|| convert_error("decimal", obj))
+}
\ No newline at end of file
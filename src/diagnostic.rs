@@ -0,0 +1,250 @@
+use logos::Span;
+use std::fmt::Write;
+use unicode_width::UnicodeWidthChar;
+use crate::error::Error;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+/// Columns a tab is printed as. Tabs are expanded so underlines stay aligned whatever the terminal's
+/// tab stops are.
+const TAB: &str = "    ";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+    pub labels: Vec<Label>,
+}
+
+pub struct Renderer<'a> {
+    source: &'a str,
+    name: Option<&'a str>,
+    color: bool,
+}
+
+struct Row {
+    col: usize,
+    len: usize,
+    primary: bool,
+    message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Self { span, message: message.into() }
+    }
+}
+
+impl Severity {
+    fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+            Severity::Note => BLUE,
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>, span: Option<Span>) -> Self {
+        Self { severity, message: message.into(), span, labels: Vec::new() }
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label::new(span, message));
+        self
+    }
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(error: &Error) -> Self {
+        let mut diagnostic = Diagnostic::new(Severity::Error, error.message(), error.span().cloned());
+        diagnostic.labels = error.labels().to_vec();
+        diagnostic
+    }
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self { source, name: None, color: false }
+    }
+
+    pub fn with_name(mut self, name: &'a str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut out = String::new();
+        let severity = diagnostic.severity;
+        let _ = writeln!(
+            out,
+            "{}{}{}: {}{}{}",
+            self.paint(severity.color()), severity.name(), self.paint(RESET),
+            self.paint(BOLD), diagnostic.message, self.paint(RESET),
+        );
+
+        let Some(span) = &diagnostic.span else {
+            return out;
+        };
+
+        let (line, col) = self.location(span.start);
+        let mut lines: Vec<(usize, Row)> = Vec::new();
+        self.rows(span, true, "", &mut lines);
+        for label in &diagnostic.labels {
+            self.rows(&label.span, false, &label.message, &mut lines);
+        }
+        lines.sort_by_key(|(line, row)| (*line, row.col));
+
+        let gutter = lines.iter().map(|(line, _)| line + 1).max().unwrap_or(1).to_string().len();
+        let pad = " ".repeat(gutter);
+        let _ = writeln!(
+            out,
+            "{}{}-->{} {}{}:{}",
+            pad, self.paint(BLUE), self.paint(RESET),
+            self.name.map_or(String::new(), |name| format!("{}:", name)), line + 1, col + 1,
+        );
+        let _ = writeln!(out, "{} {}|{}", pad, self.paint(BLUE), self.paint(RESET));
+
+        let mut previous = None;
+        for (line, row) in &lines {
+            if previous != Some(*line) {
+                if previous.is_some_and(|previous| line - previous > 1) {
+                    let _ = writeln!(out, "{}{}...{}", pad, self.paint(BLUE), self.paint(RESET));
+                }
+                let _ = writeln!(
+                    out,
+                    "{}{:>gutter$} |{} {}",
+                    self.paint(BLUE), line + 1, self.paint(RESET), self.line(*line).replace('\t', TAB),
+                );
+                previous = Some(*line);
+            }
+
+            let (marker, color) = if row.primary {
+                ("^", severity.color())
+            } else {
+                ("-", BLUE)
+            };
+            let _ = writeln!(
+                out,
+                "{} {}|{} {}{}{}{}{}",
+                pad, self.paint(BLUE), self.paint(RESET),
+                " ".repeat(row.col), self.paint(color), marker.repeat(row.len.max(1)),
+                if row.message.is_empty() { String::new() } else { format!(" {}", row.message) },
+                self.paint(RESET),
+            );
+        }
+
+        out
+    }
+
+    pub fn render_error(&self, error: &Error) -> String {
+        self.render(&Diagnostic::from(error))
+    }
+
+    fn paint(&self, code: &'static str) -> &'static str {
+        if self.color { code } else { "" }
+    }
+
+    fn line(&self, line: usize) -> &'a str {
+        self.source.lines().nth(line).unwrap_or("")
+    }
+
+    /// Zero-based line and column (in characters) of a byte offset into the source.
+    fn location(&self, offset: usize) -> (usize, usize) {
+        let (line, before) = self.prefix(offset);
+        (line, before.chars().count())
+    }
+
+    /// Zero-based line and display column of a byte offset, counting wide characters as two columns
+    /// so underlines line up with the rendered source.
+    fn display_location(&self, offset: usize) -> (usize, usize) {
+        let (line, before) = self.prefix(offset);
+        (line, width(before))
+    }
+
+    /// The line containing a byte offset and the text of that line before it. Offsets inside a
+    /// multi-byte character are moved back to its start.
+    fn prefix(&self, offset: usize) -> (usize, &'a str) {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &self.source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (before.matches('\n').count(), &before[line_start..])
+    }
+
+    /// Splits a span into one underline row per source line it covers; the message goes on the last one.
+    fn rows(&self, span: &Span, primary: bool, message: &str, lines: &mut Vec<(usize, Row)>) {
+        let (start_line, start_col) = self.display_location(span.start);
+        let (end_line, end_col) = self.display_location(span.end);
+
+        for line in start_line..=end_line {
+            let col = if line == start_line { start_col } else { 0 };
+            let end = if line == end_line { end_col } else { width(self.line(line)) };
+            let message = if line == end_line { message.to_string() } else { String::new() };
+            lines.push((line, Row { col, len: end.saturating_sub(col), primary, message }));
+        }
+    }
+}
+
+
+/// Display width of source text as rendered, with tabs expanded to `TAB`.
+fn width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { TAB.len() } else { c.width().unwrap_or(0) }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn underlines_align_after_tabs() {
+        let source = "x := 1\n\tif\t@@";
+        let start = source.find("@@").unwrap();
+        let diagnostic = Diagnostic::new(Severity::Error, "bad", Some(start..start + 2));
+        let rendered = Renderer::new(source).render(&diagnostic);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[3], "2 |     if    @@");
+        assert_eq!(lines[4], "  |           ^^");
+    }
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        let source = "界 @";
+        let diagnostic = Diagnostic::new(Severity::Error, "bad", Some(4..5));
+        let rendered = Renderer::new(source).render(&diagnostic);
+        assert_eq!(rendered.lines().nth(4), Some("  |    ^"));
+    }
+}
\ No newline at end of file
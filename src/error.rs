@@ -0,0 +1,80 @@
+use logos::Span;
+use std::fmt::Display;
+use crate::diagnostic::Label;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    Lex { message: String, span: Span },
+    Parse { message: String, span: Span, labels: Vec<Label> },
+    Runtime { message: String, span: Option<Span> },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn lex(message: impl Into<String>, span: Span) -> Self {
+        Error::Lex { message: message.into(), span }
+    }
+
+    pub fn parse(message: impl Into<String>, span: Span) -> Self {
+        Error::Parse { message: message.into(), span, labels: Vec::new() }
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        if let Error::Parse { labels, .. } = &mut self {
+            labels.push(Label::new(span, message));
+        }
+        self
+    }
+
+    pub fn runtime(message: impl Into<String>) -> Self {
+        Error::Runtime { message: message.into(), span: None }
+    }
+
+    /// Attaches `span` to a runtime error that has not been located yet.
+    pub fn with_span(mut self, span: Span) -> Self {
+        if let Error::Runtime { span: slot @ None, .. } = &mut self {
+            *slot = Some(span);
+        }
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Lex { message, .. } => message,
+            Error::Parse { message, .. } => message,
+            Error::Runtime { message, .. } => message,
+        }
+    }
+
+    pub fn labels(&self) -> &[Label] {
+        match self {
+            Error::Parse { labels, .. } => labels,
+            _ => &[],
+        }
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            Error::Lex { span, .. } => Some(span),
+            Error::Parse { span, .. } => Some(span),
+            Error::Runtime { span, .. } => span.as_ref(),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            Error::Lex { .. } => "lex error",
+            Error::Parse { .. } => "parse error",
+            Error::Runtime { .. } => "runtime error",
+        };
+        match self.span() {
+            Some(span) => write!(f, "{} at {}..{}: {}", kind, span.start, span.end, self.message()),
+            None => write!(f, "{}: {}", kind, self.message()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
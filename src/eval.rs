@@ -1,88 +1,1134 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::ops::Range;
 use std::rc::Rc;
-use crate::ast::Expr;
-use crate::obj::{Obj, ObjRef};
-use crate::profile::Profile;
+use logos::Span;
+use crate::builtins;
+use crate::methods;
+use crate::ast::{Expr, FormatSpec, MatchArm, Part, Pattern, SpannedExpr, SpannedPattern};
+use crate::error::{Error, Result};
+use crate::obj::{self, Obj, ObjRef, Scope};
+use crate::profile::{self, Profile};
 use crate::token::Token;
 
+/// Why evaluating an expression stopped without a value: an error, or a `break`, `continue` or `return`
+/// on its way to the loop or call that handles it.
+enum Unwind {
+    Error(Error),
+    Break(ObjRef),
+    Continue,
+    Return(ObjRef),
+}
+
+type Flow<T> = std::result::Result<T, Unwind>;
+
+impl From<Error> for Unwind {
+    fn from(error: Error) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+impl Unwind {
+    /// Turns a signal that escaped every loop into an error located at `span`.
+    fn into_error(self, span: Span) -> Error {
+        match self {
+            Unwind::Error(error) => error,
+            Unwind::Break(_) => Error::runtime("`break` outside of a loop").with_span(span),
+            Unwind::Continue => Error::runtime("`continue` outside of a loop").with_span(span),
+            Unwind::Return(_) => Error::runtime("`return` outside of a function").with_span(span),
+        }
+    }
+}
+
 pub struct Evaluator {
-    vars: Vec<HashMap<String, ObjRef>>,
+    vars: Vec<Scope>,
+    /// Where the current call's scopes start in `vars`. `:=` only updates variables from there on.
+    frame: usize,
+    depth: usize,
     profile: Profile,
 }
 
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Evaluator {
     pub fn new() -> Self {
+        Self::with_profile(Profile::default())
+    }
+
+    pub fn with_profile(profile: Profile) -> Self {
         Self {
-            vars: vec![HashMap::new()],
-            profile: Profile::default(),
+            vars: vec![Rc::new(RefCell::new(builtins::globals()))],
+            frame: 0,
+            depth: 0,
+            profile,
         }
     }
-    
-    pub fn eval(&mut self, expr: &Expr) -> ObjRef {
-        match expr {
-            Expr::Identifier(id) => {
-                self.vars
-                    .iter()
-                    .rev()
-                    .find_map(|scope| scope.get(id))
-                    .cloned()
-                    .unwrap_or_else(|| panic!("Undefined variable {}", id))
-                    .clone()
-            }
+
+    pub fn eval(&mut self, expr: &SpannedExpr) -> Result<ObjRef> {
+        self.evaluate(expr).map_err(|unwind| unwind.into_error(expr.span.clone()))
+    }
+
+    fn evaluate(&mut self, expr: &SpannedExpr) -> Flow<ObjRef> {
+        profile::grow(|| self.eval_expr(&expr.node)).map_err(|unwind| match unwind {
+            Unwind::Error(error) => Unwind::Error(error.with_span(expr.span.clone())),
+            unwind => unwind,
+        })
+    }
+
+    fn eval_expr(&mut self, expr: &Expr) -> Flow<ObjRef> {
+        let result = match expr {
+            Expr::Identifier(id) => self.lookup(id).ok_or_else(|| Error::runtime(format!("Undefined variable {}", id)))?,
             Expr::Number(n) => Obj::Number(*n).as_ref(),
+            Expr::Integer(n) => Obj::Int(*n).as_ref(),
+            #[cfg(feature = "bigint")]
+            Expr::BigInt(n) => Obj::BigInt(n.clone()).as_ref(),
+            #[cfg(feature = "decimal")]
+            Expr::Decimal(n) => Obj::Decimal(*n).as_ref(),
             Expr::StringLiteral(s) => Obj::String(s.clone()).as_ref(),
             Expr::Boolean(b) => Obj::Boolean(*b).as_ref(),
             Expr::Nil => Obj::Nil.as_ref(),
-            Expr::List(l) => Obj::List(l.iter().map(|e| self.eval(e)).collect()).as_ref(),
+            Expr::List(l) => Obj::List(l.iter().map(|e| self.evaluate(e)).collect::<Flow<_>>()?).as_ref(),
             Expr::Map(m) => Obj::Map(
                 m.iter()
-                    .map(|(k, v)| (match self.eval(k).into_inner() {
-                        Obj::String(s) => s,
-                        _ => panic!("Map property must be string")
-                    }, self.eval(v)))
-                    .collect(),
+                    .map(|(k, v)| Ok((match &*self.evaluate(k)?.borrow() {
+                        Obj::String(s) => s.clone(),
+                        _ => return Err(Error::runtime("Map property must be string").into())
+                    }, self.evaluate(v)?)))
+                    .collect::<Flow<_>>()?,
             ).as_ref(),
             Expr::Call(callee, args) => {
-                let callee = self.eval(callee);
-                let args = args.iter().map(|e| self.eval(e)).collect();
-                self.call(callee, args)
-            }
-            Expr::Assign(id, value) => {
-                let value = self.eval(value);
-                let id = match **id {
-                    Expr::Identifier(id) => id,
-                    _ => panic!("Expected identifier"),
+                if let Expr::Property(target, name) = &callee.node {
+                    let target = self.evaluate(target)?;
+                    let args = args.iter().map(|e| self.evaluate(e)).collect::<Flow<_>>()?;
+                    return self.method(target, name, args);
+                }
+                let callee = self.evaluate(callee)?;
+                let args = args.iter().map(|e| self.evaluate(e)).collect::<Flow<_>>()?;
+                self.call(callee, args)?
+            }
+            Expr::Pipeline(callee, args) => {
+                // An unbound name calls the piped value's method of that name instead, so
+                // `xs |> filter |x| x > 0` works like `xs.filter |x| x > 0`.
+                if let Expr::Identifier(name) = &callee.node {
+                    if self.lookup(name).is_none() {
+                        let mut args = args.iter().map(|e| self.evaluate(e)).collect::<Flow<Vec<_>>>()?;
+                        let target = args.remove(0);
+                        if methods::lookup(&target.borrow(), name).is_none() {
+                            return Err(Error::runtime(format!("Undefined variable {}", name)).with_span(callee.span.clone()).into());
+                        }
+                        return self.method(target, name, args);
+                    }
+                }
+                let callee = self.evaluate(callee)?;
+                let args = args.iter().map(|e| self.evaluate(e)).collect::<Flow<_>>()?;
+                self.call(callee, args)?
+            }
+            Expr::Assign(target, value) => {
+                let value = self.evaluate(value)?;
+                self.assign(target, value.clone())?;
+                value
+            }
+            Expr::Reassign(name, value) => {
+                let value = self.evaluate(value)?;
+                let Some(scope) = self.vars.iter().rev().find(|scope| scope.borrow().contains_key(name)) else {
+                    return Err(Error::runtime(format!("Undefined variable {}", name)).into());
                 };
-                self.vars.last_mut().unwrap().insert(id, &value);
+                scope.borrow_mut().insert(name.clone(), value.clone());
                 value
             }
             Expr::Block(exprs) => {
-                let mut result = Obj::Nil;
+                let mut result = Obj::Nil.as_ref();
                 for expr in exprs {
-                    result = self.eval(expr);
+                    result = self.evaluate(expr)?;
                 }
                 result
             }
             Expr::If(cond, then, else_) => {
-                let cond = self.eval(cond);
-                if cond.is_truthy() {
-                    self.eval(then)
+                let cond = self.evaluate(cond)?;
+                if cond.borrow().is_truthy() {
+                    self.evaluate(then)?
                 } else if let Some(else_) = else_ {
-                    self.eval(else_)
+                    self.evaluate(else_)?
                 } else {
-                    Obj::Nil
+                    Obj::Nil.as_ref()
                 }
             }
             Expr::While(cond, body) => {
-                let mut result = Obj::Nil;
-                while self.eval(cond).is_truthy() {
-                    result = self.eval(body);
+                let mut result = Obj::Nil.as_ref();
+                while self.evaluate(cond)?.borrow().is_truthy() {
+                    match self.evaluate(body) {
+                        Ok(value) => result = value,
+                        Err(Unwind::Continue) => {}
+                        Err(Unwind::Break(value)) => {
+                            result = value;
+                            break;
+                        }
+                        Err(unwind) => return Err(unwind),
+                    }
+                }
+                result
+            }
+            Expr::For(params, iterable, body) => {
+                let iterable = self.evaluate(iterable)?;
+                let mut items = Items::new(&iterable)?;
+                let mut result = Obj::Nil.as_ref();
+                while let Some(item) = self.next(&mut items)? {
+                    // Each iteration gets its own scope, so closures made in the body keep their item.
+                    self.vars.push(Rc::new(RefCell::new(HashMap::new())));
+                    let value = self.spread(params, item).map_err(Unwind::from).and_then(|()| self.evaluate(body));
+                    self.vars.pop();
+                    match value {
+                        Ok(value) => result = value,
+                        Err(Unwind::Continue) => {}
+                        Err(Unwind::Break(value)) => {
+                            result = value;
+                            break;
+                        }
+                        Err(unwind) => return Err(unwind),
+                    }
                 }
                 result
             }
-            Expr::
+            Expr::Break(value) => return Err(Unwind::Break(self.value(value)?)),
+            Expr::Continue => return Err(Unwind::Continue),
+            Expr::Return(value) => return Err(Unwind::Return(self.value(value)?)),
+            Expr::Property(obj, name) => {
+                let obj = self.evaluate(obj)?;
+                let obj = obj.borrow();
+                match &*obj {
+                    Obj::Map(m) => m.get(name).cloned().unwrap_or_else(|| Obj::Nil.as_ref()),
+                    _ => return Err(Error::runtime(format!("Cannot read property {} of {}", name, obj)).into()),
+                }
+            }
+            Expr::Index(target, index) => {
+                let target = self.evaluate(target)?;
+                let index = self.evaluate(index)?.borrow().clone();
+                let result = match (&*target.borrow(), &index) {
+                    (Obj::List(list), index) => list[position(index, list.len())?].clone(),
+                    (Obj::String(s), index) => {
+                        let chars: Vec<char> = s.chars().collect();
+                        Obj::String(chars[position(index, chars.len())?].to_string()).as_ref()
+                    }
+                    (Obj::Map(map), Obj::String(key)) => map.get(key).cloned().unwrap_or_else(|| Obj::Nil.as_ref()),
+                    (Obj::Range(range), index) => Obj::Int(range.get(position(index, range.len())?).unwrap()).as_ref(),
+                    (target, index) => return Err(Error::runtime(format!("Cannot index {} with {}", target, index)).into()),
+                };
+                result
+            }
+            Expr::Slice(target, from, to) => {
+                let target = self.evaluate(target)?;
+                let from = from.as_ref().map(|from| self.evaluate(from)).transpose()?;
+                let to = to.as_ref().map(|to| self.evaluate(to)).transpose()?;
+                let (from, to) = (from.map(|from| from.borrow().clone()), to.map(|to| to.borrow().clone()));
+                let result = match &*target.borrow() {
+                    Obj::List(list) => Obj::List(list[bounds(from.as_ref(), to.as_ref(), list.len())?].to_vec()),
+                    Obj::String(s) => {
+                        let range = bounds(from.as_ref(), to.as_ref(), s.chars().count())?;
+                        Obj::String(s.chars().skip(range.start).take(range.len()).collect())
+                    }
+                    Obj::Range(range) => Obj::Range(range.slice(bounds(from.as_ref(), to.as_ref(), range.len())?)),
+                    obj => return Err(Error::runtime(format!("Cannot slice {}", obj)).into()),
+                };
+                result.as_ref()
+            }
+            Expr::Range(start, end, inclusive, step) => {
+                let int = |obj: ObjRef| match *obj.borrow() {
+                    Obj::Int(n) => Ok(n),
+                    ref obj => Err(Error::runtime(format!("Range bounds must be integers, not {}", obj))),
+                };
+                let start = int(self.evaluate(start)?)?;
+                let end = int(self.evaluate(end)?)?;
+                let step = match step {
+                    Some(step) => int(self.evaluate(step)?)?,
+                    None => 1,
+                };
+                Obj::Range(obj::Range::new(start, end, *inclusive, step)?).as_ref()
+            }
+            Expr::Binary(left, op @ (Token::And | Token::Or), right) => {
+                // Short-circuits and yields the operand that decided the result, so `x or default` works.
+                let left = self.evaluate(left)?;
+                let truthy = left.borrow().is_truthy();
+                if truthy == (*op == Token::Or) {
+                    left
+                } else {
+                    self.evaluate(right)?
+                }
+            }
+            Expr::Binary(left, Token::Operator(op), right) => {
+                // A user-defined operator calls the value bound to its name by `infix`.
+                let callee = self.lookup(op).ok_or_else(|| Error::runtime(format!("Undefined operator {}", op)))?;
+                let args = vec![self.evaluate(left)?, self.evaluate(right)?];
+                self.call(callee, args)?
+            }
+            Expr::Binary(left, op, right) => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                let result = binary(&left.borrow(), op, &right.borrow())?;
+                result.as_ref()
+            }
+            Expr::Unary(op, right) => {
+                let right = self.evaluate(right)?;
+                let right = right.borrow();
+                match (op, &*right) {
+                    (Token::Minus, Obj::Number(n)) => Obj::Number(-n).as_ref(),
+                    (Token::Minus, Obj::Int(n)) => Obj::Int(n.checked_neg().ok_or_else(|| Error::runtime("Integer overflow"))?).as_ref(),
+                    #[cfg(feature = "bigint")]
+                    (Token::Minus, Obj::BigInt(n)) => Obj::BigInt(-n).as_ref(),
+                    #[cfg(feature = "decimal")]
+                    (Token::Minus, Obj::Decimal(n)) => Obj::Decimal(-n).as_ref(),
+                    (Token::Not, obj) => Obj::Boolean(!obj.is_truthy()).as_ref(),
+                    (op, obj) => return Err(Error::runtime(format!("Invalid operand for {}: {}", op, obj)).into()),
+                }
+            }
+            Expr::Lambda(params, body) => Obj::Closure {
+                params: params.clone(),
+                body: (**body).clone(),
+                env: self.vars.clone(),
+            }.as_ref(),
+            Expr::Match(subject, arms, default) => {
+                let subject = self.evaluate(subject)?;
+                for arm in arms {
+                    let mut scope = HashMap::new();
+                    if !bind(&arm.pattern, &subject, &mut scope) {
+                        continue;
+                    }
+
+                    self.vars.push(Rc::new(RefCell::new(scope)));
+                    let result = self.arm(arm);
+                    self.vars.pop();
+                    if let Some(result) = result? {
+                        return Ok(result);
+                    }
+                }
+                match default {
+                    Some(default) => self.evaluate(default)?,
+                    None => Obj::Nil.as_ref(),
+                }
+            }
+            Expr::Interpolate(parts) => {
+                let mut result = String::new();
+                for part in parts {
+                    match part {
+                        Part::Text(text) => result.push_str(text),
+                        Part::Expr(expr, None) => result.push_str(&self.evaluate(expr)?.borrow().to_string()),
+                        Part::Expr(expr, Some(spec)) => result.push_str(&format(&self.evaluate(expr)?.borrow(), spec)),
+                    }
+                }
+                Obj::String(result).as_ref()
+            }
+            Expr::Error => return Err(Error::runtime("Cannot evaluate an expression that failed to parse").into()),
+        };
+
+        Ok(result)
+    }
+
+    fn lookup(&self, name: &str) -> Option<ObjRef> {
+        self.vars.iter().rev().find_map(|scope| scope.borrow().get(name).cloned())
+    }
+
+    /// Binds `value` to an assignment target, destructuring lists and maps. Nested targets written
+    /// `target := default` fall back to their default when the value is nil.
+    fn assign(&mut self, target: &SpannedExpr, value: ObjRef) -> Flow<()> {
+        match &target.node {
+            Expr::Identifier(id) => {
+                // Updates a binding made earlier in the same call, such as a total kept across loop
+                // iterations, and defines a new one in the innermost scope otherwise. Variables of
+                // callers and enclosing functions are only shadowed; `=` updates those.
+                let scope = self.vars[self.frame..].iter().rev().find(|scope| scope.borrow().contains_key(id));
+                let scope = scope.unwrap_or_else(|| self.vars.last().unwrap());
+                scope.borrow_mut().insert(id.clone(), value);
+            }
+            Expr::Index(target, index) => {
+                let target = self.evaluate(target)?;
+                let index = self.evaluate(index)?.borrow().clone();
+                match (&mut *target.borrow_mut(), &index) {
+                    (Obj::List(list), index) => {
+                        let i = position(index, list.len())?;
+                        list[i] = value;
+                    }
+                    (Obj::Map(map), Obj::String(key)) => {
+                        map.insert(key.clone(), value);
+                    }
+                    (target, index) => return Err(Error::runtime(format!("Cannot assign to index {} of {}", index, target)).into()),
+                };
+            }
+            Expr::Assign(target, default) => {
+                let value = if matches!(*value.borrow(), Obj::Nil) {
+                    self.evaluate(default)?
+                } else {
+                    value
+                };
+                self.assign(target, value)?;
+            }
+            Expr::List(items) => {
+                let list = match &*value.borrow() {
+                    Obj::List(list) => list.clone(),
+                    obj => return Err(Error::runtime(format!("Cannot destructure {} as a list", obj)).with_span(target.span.clone()).into()),
+                };
+                for (i, item) in items.iter().enumerate() {
+                    self.assign(item, list.get(i).cloned().unwrap_or_else(|| Obj::Nil.as_ref()))?;
+                }
+            }
+            Expr::Map(entries) => {
+                let map = match &*value.borrow() {
+                    Obj::Map(map) => map.clone(),
+                    obj => return Err(Error::runtime(format!("Cannot destructure {} as a map", obj)).with_span(target.span.clone()).into()),
+                };
+                for (key, item) in entries {
+                    let key = match &*self.evaluate(key)?.borrow() {
+                        Obj::String(s) => s.clone(),
+                        _ => return Err(Error::runtime("Map property must be string").with_span(key.span.clone()).into()),
+                    };
+                    self.assign(item, map.get(&key).cloned().unwrap_or_else(|| Obj::Nil.as_ref()))?;
+                }
+            }
+            _ => return Err(Error::runtime("Invalid assignment target").with_span(target.span.clone()).into()),
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates the optional value of `break` or `return`, which defaults to nil.
+    fn value(&mut self, value: &Option<Box<SpannedExpr>>) -> Flow<ObjRef> {
+        match value {
+            Some(value) => self.evaluate(value),
+            None => Ok(Obj::Nil.as_ref()),
+        }
+    }
+
+    fn next(&mut self, items: &mut Items) -> Flow<Option<ObjRef>> {
+        match items {
+            Items::Snapshot(items) => Ok(items.next()),
+            Items::Range(range, index) => {
+                let item = range.get(*index);
+                *index += 1;
+                Ok(item.map(|n| Obj::Int(n).as_ref()))
+            }
+            Items::Next(next, iterator) => {
+                let item = self.call(next.clone(), vec![iterator.clone()])?;
+                let done = matches!(*item.borrow(), Obj::Nil);
+                Ok((!done).then_some(item))
+            }
+        }
+    }
+
+    /// Binds a loop item to the loop variables in the iteration's scope. Several variables take the
+    /// elements of a list item in order.
+    fn spread(&mut self, params: &[String], item: ObjRef) -> Result<()> {
+        let mut scope = self.vars.last().unwrap().borrow_mut();
+        if let [param] = params {
+            scope.insert(param.clone(), item);
+            return Ok(());
+        }
+        
+        let item = item.borrow();
+        let Obj::List(values) = &*item else {
+            return Err(Error::runtime(format!("Cannot destructure {} into {} loop variables", item, params.len())));
+        };
+        for (i, param) in params.iter().enumerate() {
+            scope.insert(param.clone(), values.get(i).cloned().unwrap_or_else(|| Obj::Nil.as_ref()));
+        }
+        Ok(())
+    }
+
+    /// Evaluates an arm whose pattern has matched, or returns `None` if its guard rejects the subject.
+    fn arm(&mut self, arm: &MatchArm) -> Flow<Option<ObjRef>> {
+        if let Some(guard) = &arm.guard {
+            if !self.evaluate(guard)?.borrow().is_truthy() {
+                return Ok(None);
+            }
+        }
+        self.evaluate(&arm.body).map(Some)
+    }
+
+    /// Calls `target.name(args)`: a map's own entry when it has one, otherwise a native method of the
+    /// target's type.
+    fn method(&mut self, target: ObjRef, name: &str, args: Vec<ObjRef>) -> Flow<ObjRef> {
+        let entry = match &*target.borrow() {
+            Obj::Map(map) => map.get(name).cloned(),
+            _ => None,
+        };
+        if let Some(entry) = entry {
+            return Ok(self.call(entry, args)?);
+        }
+
+        let Some(method) = methods::lookup(&target.borrow(), name) else {
+            return Err(Error::runtime(format!("No method {} on {}", name, target.borrow())).into());
+        };
+        Ok(method(&target, &args, &mut |callee, args| self.call(callee.clone(), args))?)
+    }
+
+    /// Calls a closure or builtin. Any jump left unhandled by the body becomes an error here.
+    fn call(&mut self, callee: ObjRef, args: Vec<ObjRef>) -> Result<ObjRef> {
+        let (params, body, env) = match &*callee.borrow() {
+            Obj::Closure { params, body, env } => (params.clone(), body.clone(), env.clone()),
+            Obj::Builtin { func, .. } => return func(&args),
+            // Calling a map looks up a key, so `ops(t)` is `ops[t]`.
+            Obj::Map(map) => {
+                return match args.as_slice() {
+                    [key] => match &*key.borrow() {
+                        Obj::String(key) => Ok(map.get(key).cloned().unwrap_or_else(|| Obj::Nil.as_ref())),
+                        key => Err(Error::runtime(format!("Map keys are strings, not {}", key))),
+                    },
+                    _ => Err(Error::runtime(format!("A map expects 1 argument, got {}", args.len()))),
+                };
+            }
+            obj => return Err(Error::runtime(format!("Cannot call {}", obj))),
+        };
+
+        let mut scope = HashMap::new();
+        let mut args = args.into_iter();
+        for param in params {
+            scope.insert(param, args.next().unwrap_or_else(|| Obj::Nil.as_ref()));
+        }
+
+        if self.profile.max_stack_depth.is_some_and(|max| self.depth >= max) {
+            return Err(Error::runtime("Stack overflow"));
+        }
+
+        let caller_vars = std::mem::replace(&mut self.vars, env);
+        let caller_frame = std::mem::replace(&mut self.frame, self.vars.len());
+        self.vars.push(Rc::new(RefCell::new(scope)));
+        self.depth += 1;
+        let result = self.evaluate(&body);
+        self.depth -= 1;
+        self.vars = caller_vars;
+        self.frame = caller_frame;
+        match result {
+            Ok(value) | Err(Unwind::Return(value)) => Ok(value),
+            Err(unwind) => Err(unwind.into_error(body.span.clone())),
+        }
+    }
+}
+
+/// The items a `for` loop visits: a snapshot of a collection taken when the loop starts, a range's
+/// integers from the given index on, or the values returned by an iterator's `next` closure until it
+/// returns nil. `next` is passed the iterator map, so its state can live either in the map or in the
+/// variables the closure captured:
+///
+/// ```text
+/// countdown := ('n': 3, 'next': |it| if it['n'] > 0 then { it['n'] := it['n'] - 1; it['n'] + 1 })
+/// for (countdown) |n| ...   # 3, 2, 1
+/// ```
+enum Items {
+    Snapshot(std::vec::IntoIter<ObjRef>),
+    Range(obj::Range, usize),
+    Next(ObjRef, ObjRef),
+}
+
+impl Items {
+    /// Lists yield their elements, strings their characters, ranges their integers and maps
+    /// `(key, value)` pairs, unless the map has a callable `next`, which makes it an iterator.
+    fn new(obj: &ObjRef) -> Result<Self> {
+        let items: Vec<ObjRef> = match &*obj.borrow() {
+            Obj::List(list) => list.clone(),
+            Obj::String(s) => s.chars().map(|c| Obj::String(c.to_string()).as_ref()).collect(),
+            Obj::Range(range) => return Ok(Items::Range(*range, 0)),
+            Obj::Map(map) => match map.get("next") {
+                Some(next) if matches!(*next.borrow(), Obj::Closure { .. } | Obj::Builtin { .. }) => {
+                    return Ok(Items::Next(next.clone(), obj.clone()));
+                }
+                _ => map
+                    .iter()
+                    .map(|(key, value)| Obj::List(vec![Obj::String(key.clone()).as_ref(), value.clone()]).as_ref())
+                    .collect(),
+            },
+            obj => return Err(Error::runtime(format!("Cannot iterate over {}", obj))),
+        };
+        Ok(Items::Snapshot(items.into_iter()))
+    }
+}
+
+/// Applies a built-in binary operator other than the short-circuiting `and` and `or`.
+pub fn binary(left: &Obj, op: &Token, right: &Obj) -> Result<Obj> {
+    #[cfg(feature = "decimal")]
+    if let Some(result) = decimal(left, op, right) {
+        return result;
+    }
+    #[cfg(feature = "bigint")]
+    if let Some(result) = bigint(left, op, right) {
+        return result;
+    }
+    if let (Obj::Int(a), Obj::Int(b)) = (left, right) {
+        if let Some(result) = integer(*a, op, *b) {
+            return result;
+        }
+    }
+    if let (Some(a), Some(b)) = (left.as_number(), right.as_number()) {
+        if let Some(result) = number(a, op, b) {
+            return Ok(result);
+        }
+    }
+
+    let result = match (left, op, right) {
+        (Obj::String(a), Token::Plus, b) => Obj::String(format!("{}{}", a, b)),
+        (Obj::List(a), Token::Plus, Obj::List(b)) => Obj::List(a.iter().chain(b).cloned().collect()),
+        // `text / separator` splits the text into a list of strings.
+        (Obj::String(a), Token::Slash, Obj::String(b)) if !b.is_empty() => {
+            Obj::List(a.split(b.as_str()).map(|part| Obj::String(part.to_string()).as_ref()).collect())
+        }
+        (a, Token::Equality, b) => Obj::Boolean(a == b),
+        (a, Token::NotEqual, b) => Obj::Boolean(a != b),
+        (Obj::String(a), Token::LessThan, Obj::String(b)) => Obj::Boolean(a < b),
+        (Obj::String(a), Token::GreaterThan, Obj::String(b)) => Obj::Boolean(a > b),
+        (Obj::String(a), Token::LessThanEqual, Obj::String(b)) => Obj::Boolean(a <= b),
+        (Obj::String(a), Token::GreaterThanEqual, Obj::String(b)) => Obj::Boolean(a >= b),
+        (a, op, b) => return Err(Error::runtime(format!("Invalid operands for {}: {} and {}", op, a, b))),
+    };
+
+    Ok(result)
+}
+
+/// Resolves an index into a collection of `len` elements, counting negative indices from the end.
+pub fn position(index: &Obj, len: usize) -> Result<usize> {
+    let Obj::Int(i) = index else {
+        return Err(Error::runtime(format!("Index must be an integer, not {}", index)));
+    };
+    let resolved = if *i < 0 { *i as i128 + len as i128 } else { *i as i128 };
+    if (0..len as i128).contains(&resolved) {
+        Ok(resolved as usize)
+    } else {
+        Err(Error::runtime(format!("Index {} out of bounds for length {}", i, len)))
+    }
+}
+
+/// Resolves the bounds of a slice, which default to the whole collection and count from the end when
+/// negative.
+fn bounds(from: Option<&Obj>, to: Option<&Obj>, len: usize) -> Result<Range<usize>> {
+    let resolve = |bound: Option<&Obj>, default: usize| match bound {
+        None => Ok(default as i128),
+        Some(Obj::Int(i)) if *i < 0 => Ok(*i as i128 + len as i128),
+        Some(Obj::Int(i)) => Ok(*i as i128),
+        Some(obj) => Err(Error::runtime(format!("Slice bounds must be integers, not {}", obj))),
+    };
+    let (start, end) = (resolve(from, 0)?, resolve(to, len)?);
+    if 0 <= start && start <= end && end <= len as i128 {
+        Ok(start as usize..end as usize)
+    } else {
+        let text = |bound: Option<&Obj>| bound.map(|bound| bound.to_string()).unwrap_or_default();
+        Err(Error::runtime(format!("Slice {}..{} out of bounds for length {}", text(from), text(to), len)))
+    }
+}
+
+/// Integer arithmetic is checked for overflow. `/` and `%` floor, so a remainder takes the sign of the
+/// divisor. Returns `None` for operators that integers don't handle specially.
+fn integer(a: i64, op: &Token, b: i64) -> Option<Result<Obj>> {
+    let result = match op {
+        Token::Plus => a.checked_add(b),
+        Token::Minus => a.checked_sub(b),
+        Token::Star => a.checked_mul(b),
+        Token::Slash | Token::Percent if b == 0 => return Some(Err(Error::runtime("Division by zero"))),
+        Token::Slash => a.checked_div(b).map(|q| if a % b != 0 && (a < 0) != (b < 0) { q - 1 } else { q }),
+        Token::Percent => a.checked_rem(b).map(|r| if r != 0 && (r < 0) != (b < 0) { r + b } else { r }),
+        Token::LessThan => return Some(Ok(Obj::Boolean(a < b))),
+        Token::GreaterThan => return Some(Ok(Obj::Boolean(a > b))),
+        Token::LessThanEqual => return Some(Ok(Obj::Boolean(a <= b))),
+        Token::GreaterThanEqual => return Some(Ok(Obj::Boolean(a >= b))),
+        _ => return None,
+    };
+    Some(result.map(Obj::Int).ok_or_else(|| Error::runtime("Integer overflow")))
+}
+
+/// Exact integer arithmetic once either operand is a `BigInt`, with the same flooring `/` and `%` as
+/// `Int`. Returns `None` for other operands, so mixing with a `Number` falls through to floats.
+#[cfg(feature = "bigint")]
+fn bigint(left: &Obj, op: &Token, right: &Obj) -> Option<Result<Obj>> {
+    use num_bigint::BigInt;
+    use num_integer::Integer;
+    use num_traits::Zero;
+
+    let (a, b) = match (left, right) {
+        (Obj::BigInt(a), Obj::BigInt(b)) => (a.clone(), b.clone()),
+        (Obj::BigInt(a), Obj::Int(b)) => (a.clone(), BigInt::from(*b)),
+        (Obj::Int(a), Obj::BigInt(b)) => (BigInt::from(*a), b.clone()),
+        _ => return None,
+    };
+    let result = match op {
+        Token::Plus => a + b,
+        Token::Minus => a - b,
+        Token::Star => a * b,
+        Token::Slash | Token::Percent if b.is_zero() => return Some(Err(Error::runtime("Division by zero"))),
+        Token::Slash => a.div_floor(&b),
+        Token::Percent => a.mod_floor(&b),
+        Token::LessThan => return Some(Ok(Obj::Boolean(a < b))),
+        Token::GreaterThan => return Some(Ok(Obj::Boolean(a > b))),
+        Token::LessThanEqual => return Some(Ok(Obj::Boolean(a <= b))),
+        Token::GreaterThanEqual => return Some(Ok(Obj::Boolean(a >= b))),
+        _ => return None,
+    };
+    Some(Ok(Obj::BigInt(result)))
+}
+
+/// Exact decimal arithmetic once either operand is a `Decimal`; integers convert losslessly. Floats
+/// are rejected rather than rounded, so they must go through `decimal()` first.
+#[cfg(feature = "decimal")]
+fn decimal(left: &Obj, op: &Token, right: &Obj) -> Option<Result<Obj>> {
+    use rust_decimal::Decimal;
+
+    fn exact(obj: &Obj) -> Option<Decimal> {
+        match obj {
+            Obj::Decimal(n) => Some(*n),
+            Obj::Int(n) => Some(Decimal::from(*n)),
+            #[cfg(feature = "bigint")]
+            Obj::BigInt(n) => n.to_string().parse().ok(),
+            _ => None,
+        }
+    }
+
+    let (a, b) = match (left, right) {
+        (Obj::Decimal(_), _) | (_, Obj::Decimal(_)) => (exact(left)?, exact(right)?),
+        _ => return None,
+    };
+    let result = match op {
+        Token::Plus => a.checked_add(b),
+        Token::Minus => a.checked_sub(b),
+        Token::Star => a.checked_mul(b),
+        Token::Slash | Token::Percent if b.is_zero() => return Some(Err(Error::runtime("Division by zero"))),
+        Token::Slash => a.checked_div(b),
+        Token::Percent => a.checked_rem(b),
+        Token::LessThan => return Some(Ok(Obj::Boolean(a < b))),
+        Token::GreaterThan => return Some(Ok(Obj::Boolean(a > b))),
+        Token::LessThanEqual => return Some(Ok(Obj::Boolean(a <= b))),
+        Token::GreaterThanEqual => return Some(Ok(Obj::Boolean(a >= b))),
+        _ => return None,
+    };
+    Some(result.map(Obj::Decimal).ok_or_else(|| Error::runtime("Decimal overflow")))
+}
+
+/// Floating point arithmetic, used once either operand is a `Number`.
+fn number(a: f64, op: &Token, b: f64) -> Option<Obj> {
+    let result = match op {
+        Token::Plus => Obj::Number(a + b),
+        Token::Minus => Obj::Number(a - b),
+        Token::Star => Obj::Number(a * b),
+        Token::Slash => Obj::Number(a / b),
+        Token::Percent => Obj::Number(a % b),
+        Token::LessThan => Obj::Boolean(a < b),
+        Token::GreaterThan => Obj::Boolean(a > b),
+        Token::LessThanEqual => Obj::Boolean(a <= b),
+        Token::GreaterThanEqual => Obj::Boolean(a >= b),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Matches `value` against `pattern`, collecting the variables it binds into `scope`.
+fn bind(pattern: &SpannedPattern, value: &ObjRef, scope: &mut HashMap<String, ObjRef>) -> bool {
+    match (&pattern.node, &*value.borrow()) {
+        (Pattern::Wildcard, _) => true,
+        (Pattern::Binding(name), _) => {
+            scope.insert(name.clone(), value.clone());
+            true
+        }
+        (Pattern::Number(a), Obj::Number(b)) => a == b,
+        (Pattern::Number(a), Obj::Int(b)) => *a == *b as f64,
+        (Pattern::Integer(a), Obj::Int(b)) => a == b,
+        (Pattern::Integer(a), Obj::Number(b)) => *a as f64 == *b,
+        (Pattern::StringLiteral(a), Obj::String(b)) => a == b,
+        (Pattern::Boolean(a), Obj::Boolean(b)) => a == b,
+        (Pattern::Nil, Obj::Nil) => true,
+        (Pattern::List(items, rest), Obj::List(list)) => {
+            if list.len() < items.len() || (rest.is_none() && list.len() != items.len()) {
+                return false;
+            }
+            items.iter().zip(list).all(|(item, value)| bind(item, value, scope))
+                && rest.as_ref().is_none_or(|rest| bind(rest, &Obj::List(list[items.len()..].to_vec()).as_ref(), scope))
         }
+        (Pattern::Map(entries), Obj::Map(map)) => entries
+            .iter()
+            .all(|(key, entry)| map.get(key).is_some_and(|value| bind(entry, value, scope))),
+        _ => false,
     }
 }
-    
\ No newline at end of file
+
+/// Renders `obj` through a format spec: precision fixes the decimals of numbers and truncates other
+/// values, then the result is padded to the width. Numbers align right by default, other values left.
+fn format(obj: &Obj, spec: &FormatSpec) -> String {
+    let body = match (obj, spec.precision) {
+        (Obj::Number(n), Some(precision)) => format!("{:.*}", precision, n),
+        (Obj::Int(n), Some(0)) => n.to_string(),
+        (Obj::Int(n), Some(precision)) => format!("{}.{}", n, "0".repeat(precision)),
+        #[cfg(feature = "bigint")]
+        (Obj::BigInt(n), Some(0)) => n.to_string(),
+        #[cfg(feature = "bigint")]
+        (Obj::BigInt(n), Some(precision)) => format!("{}.{}", n, "0".repeat(precision)),
+        #[cfg(feature = "decimal")]
+        (Obj::Decimal(n), Some(precision)) => format!("{:.*}", precision, n),
+        (obj, Some(precision)) => obj.to_string().chars().take(precision).collect(),
+        (obj, None) => obj.to_string(),
+    };
+    
+    let width = spec.width.unwrap_or(0);
+    let padding = width.saturating_sub(body.chars().count());
+    if padding == 0 {
+        return body;
+    }
+    
+    let numeric = obj.is_numeric();
+    if spec.zero && spec.align.is_none() && numeric {
+        let (sign, digits) = body.split_at(usize::from(body.starts_with('-')));
+        return format!("{}{}{}", sign, "0".repeat(padding), digits);
+    }
+    
+    let fill = |n: usize| spec.fill.to_string().repeat(n);
+    match spec.align.unwrap_or(if numeric { '>' } else { '<' }) {
+        '>' => format!("{}{}", fill(padding), body),
+        '^' => format!("{}{}{}", fill(padding / 2), body, fill(padding - padding / 2)),
+        _ => format!("{}{}", body, fill(padding)),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::token;
+
+    fn run(source: &str) -> Result<Obj> {
+        let tokens = token::lex(source)?;
+        let (exprs, errors) = Parser::new(&tokens).parse_all();
+        assert_eq!(errors, Vec::new());
+        let mut evaluator = Evaluator::new();
+        let mut result = Obj::Nil.as_ref();
+        for expr in &exprs {
+            result = evaluator.eval(expr)?;
+        }
+        let result = result.borrow().clone();
+        Ok(result)
+    }
+
+    fn ints(values: &[i64]) -> Obj {
+        Obj::List(values.iter().map(|&n| Obj::Int(n).as_ref()).collect())
+    }
+
+    #[test]
+    fn runaway_recursion_is_an_error() {
+        let error = run("f := |g| g(g)\nf(f)").unwrap_err();
+        assert!(matches!(error, Error::Runtime { ref message, .. } if message == "Stack overflow"));
+    }
+
+    #[test]
+    fn deep_nesting_is_a_parse_error() {
+        let source = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        let tokens = token::lex(&source).unwrap();
+        let (_, errors) = Parser::new(&tokens).parse_all();
+        assert!(matches!(&errors[..], [Error::Parse { message, .. }] if message == "Expression is nested too deeply"));
+    }
+
+    #[test]
+    fn prefix_binds_tighter_than_declared_operators() {
+        assert_eq!(run("infix 9 <+> := |a, b| a * 10 + b\n-1 <+> 2"), Ok(Obj::Int(-8)));
+    }
+
+    #[test]
+    fn index_with_any_expression() {
+        assert_eq!(run("xs := (10, 20, 30)\ni := nil\nxs[i or 2]"), Ok(Obj::Int(30)));
+        assert_eq!(run("m := ('true': 1)\nm['{1 == 1}']"), Ok(Obj::Int(1)));
+        assert_eq!(run("xs := (10, 20, 30)\na := 1\nxs[a..a + 1]"), Ok(ints(&[20])));
+    }
+
+    #[test]
+    fn nested_placeholders() {
+        assert_eq!(run("(_ + _ * 2)(1, 2)"), Ok(Obj::Int(5)));
+        assert_eq!(run("(-_ - 1)(5)"), Ok(Obj::Int(-6)));
+    }
+
+    #[test]
+    fn pipeline_into_list_methods() {
+        assert_eq!(run("(3, -1, 2) |> filter |x| x > 0"), Ok(ints(&[3, 2])));
+        assert_eq!(run("(3, -1, 2) |> filter(|x| x > 0) |> map((_ * 10))"), Ok(ints(&[30, 20])));
+        assert!(run("(3, -1, 2) |> nothing").is_err());
+    }
+
+    #[test]
+    fn only_pipelines_fall_back_to_methods() {
+        assert_eq!(run("len := |xs| 0\n(1, 2) |> len"), Ok(Obj::Int(0)));
+        assert_eq!(run("(1, 2) |> len"), Ok(Obj::Int(2)));
+        let error = run("xs := (1,)\npush(xs, 3)").unwrap_err();
+        assert!(matches!(error, Error::Runtime { ref message, .. } if message == "Undefined variable push"));
+    }
+
+    #[test]
+    fn trailing_lambdas_end_at_the_next_stage() {
+        assert_eq!(run("(3, -1, 2) |> filter |x| x > 0 |> map |x| x * 10"), Ok(ints(&[30, 20])));
+        assert_eq!(run("(3, -1, 2)\n  |> filter |x| x > 0\n  |> map |x| x * 10"), Ok(ints(&[30, 20])));
+        assert_eq!(run("(1, 2).map |x| x + 1 |> reverse"), Ok(ints(&[3, 2])));
+    }
+
+    #[test]
+    fn functions_shadow_outer_variables() {
+        assert_eq!(run("result := 5\ndouble := |x| { result := x * 2; result }\ndouble(1)\nresult"), Ok(Obj::Int(5)));
+        assert_eq!(run("x := 1\nf := |x| { x := x + 1; x }\nf(10) + x"), Ok(Obj::Int(12)));
+    }
+
+    #[test]
+    fn reassignment_updates_captured_variables() {
+        assert_eq!(run("count := 0\ntick := || count = count + 1\ntick()\ntick()\ncount"), Ok(Obj::Int(2)));
+        let error = run("f := || missing = 1\nf()").unwrap_err();
+        assert!(matches!(error, Error::Runtime { ref message, .. } if message == "Undefined variable missing"));
+        let (_, errors) = Parser::new(&token::lex("xs := (1,)\nxs[0] = 2").unwrap()).parse_all();
+        assert!(matches!(&errors[..], [Error::Parse { message, .. }] if message == "Only variables can be reassigned with `=`"));
+    }
+
+    #[test]
+    fn loop_variables_are_fresh_each_iteration() {
+        assert_eq!(run("fs := (,)\nfor ((1, 2, 3)) |i| fs.push(|| i)\nfs.map(|f| f())"), Ok(ints(&[1, 2, 3])));
+        assert_eq!(run("i := 10\nfor ((1, 2)) |i| i\ni"), Ok(Obj::Int(10)));
+        assert!(run("for ((1, 2)) |i| { j := i; break }\nj").is_err());
+        assert_eq!(run("total := 0\nfor ((1, 2, 3)) |i| total := total + i\ntotal"), Ok(Obj::Int(6)));
+    }
+
+    #[test]
+    fn break_and_continue() {
+        assert_eq!(run("for ((1, 2, 3)) |i| if i == 2 then break i * 10"), Ok(Obj::Int(20)));
+        assert_eq!(run("i := 0\nwhile true { i := i + 1; if i == 3 then break }"), Ok(Obj::Nil));
+        assert_eq!(run("i := 0\nwhile true { i := i + 1; if i == 3 then break i }"), Ok(Obj::Int(3)));
+        assert_eq!(run("odd := (,)\nfor ((1, 2, 3, 4)) |i| { if i % 2 == 0 then continue; odd.push(i) }\nodd"), Ok(ints(&[1, 3])));
+    }
+
+    #[test]
+    fn return_leaves_the_function() {
+        assert_eq!(run("f := |x| { if x > 0 then return 'positive'; 'other' }\nf(1)"), Ok(Obj::String("positive".into())));
+        assert_eq!(run("f := || { for ((1, 2, 3)) |i| if i == 2 then return i; 0 }\nf()"), Ok(Obj::Int(2)));
+        assert_eq!(run("f := || { return; 1 }\nf()"), Ok(Obj::Nil));
+    }
+
+    fn parse_errors(source: &str) -> Vec<String> {
+        let (_, errors) = Parser::new(&token::lex(source).unwrap()).parse_all();
+        errors.iter().map(|error| error.message().to_string()).collect()
+    }
+
+    #[test]
+    fn parse_errors_are_recovered_from() {
+        let tokens = token::lex("a := )\nb := 2\nc := * 3; d := 4\ne := ) xs[1; 2]\nf := 5").unwrap();
+        let (exprs, errors) = Parser::new(&tokens).parse_all();
+        let messages: Vec<&str> = errors.iter().map(|error| error.message()).collect();
+        assert_eq!(messages, vec!["Expected an expression, found `)`", "Expected an expression, found `*`", "Expected an expression, found `)`"]);
+        let statements: Vec<&str> = exprs
+            .iter()
+            .map(|expr| match &expr.node {
+                Expr::Assign(target, _) => match &target.node {
+                    Expr::Identifier(name) => name.as_str(),
+                    _ => panic!("unexpected target {:?}", target),
+                },
+                Expr::Error => "error",
+                node => panic!("unexpected statement {:?}", node),
+            })
+            .collect();
+        assert_eq!(statements, vec!["error", "b", "error", "d", "error", "f"]);
+    }
+
+    #[test]
+    fn nested_destructuring_with_defaults() {
+        assert_eq!(run("(a, (b, c := 3)) := (1, (2,))\n(a, b, c)"), Ok(ints(&[1, 2, 3])));
+        assert_eq!(run("(a, b := 2, c := a) := (1, nil)\n(a, b, c)"), Ok(ints(&[1, 2, 1])));
+        assert_eq!(run("('x': x, 'y': (p, q := 0)) := ('x': 1, 'y': (5,))\n(x, p, q)"), Ok(ints(&[1, 5, 0])));
+        assert_eq!(run("('x': x := 7, 'y': ('z': z)) := ('y': ('z': 2))\n(x, z)"), Ok(ints(&[7, 2])));
+    }
+
+    #[test]
+    fn destructuring_the_wrong_kind_of_value() {
+        assert_eq!(run("(a, b) := 5").unwrap_err().message(), "Cannot destructure 5 as a list");
+        assert_eq!(run("('k': v) := (1,)").unwrap_err().message(), "Cannot destructure (1,) as a map");
+        assert_eq!(run("(a, ('k': v)) := (1, (2,))").unwrap_err().message(), "Cannot destructure (2,) as a map");
+        assert_eq!(parse_errors("(a, 1) := (1, 2)"), vec!["Invalid assignment target"]);
+        assert_eq!(parse_errors("a := 1 := 2"), vec!["Invalid assignment target"]);
+        assert_eq!(parse_errors("f() := 1"), vec!["Invalid assignment target"]);
+    }
+
+    fn warnings(source: &str) -> Vec<String> {
+        let tokens = token::lex(source).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let (_, errors) = parser.parse_all();
+        assert_eq!(errors, Vec::new());
+        parser.take_warnings().into_iter().map(|warning| warning.message).collect()
+    }
+
+    #[test]
+    fn boolean_and_nil_exhaustiveness() {
+        assert_eq!(warnings("match x { true: 1 }"), vec!["Non-exhaustive match: `false` and `nil` not covered"]);
+        assert_eq!(warnings("match x { true: 1, false: 2 }"), vec!["Non-exhaustive match: `nil` not covered"]);
+        assert_eq!(warnings("match x { nil: 1 }"), vec!["Non-exhaustive match: `true` and `false` not covered"]);
+        assert_eq!(warnings("match x { true: 1, false: 2, nil: 3 }"), Vec::<String>::new());
+        assert_eq!(warnings("match x { true: 1, nil if y: 2, false: 3 }"), vec!["Non-exhaustive match: `nil` not covered"]);
+        // Other patterns make it a match over some other type, where only the booleans are checked.
+        assert_eq!(warnings("match x { 1: 1, true: 2 }"), vec!["Non-exhaustive match: `false` not covered"]);
+        assert_eq!(warnings("match x { (a, b): 1, nil: 2 }"), Vec::<String>::new());
+        assert_eq!(warnings("match x { true: 1, _: 2 }"), Vec::<String>::new());
+        assert_eq!(warnings("match x { true: 1, else: 2 }"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn jumps_outside_their_construct_are_parse_errors() {
+        assert_eq!(parse_errors("break"), vec!["`break` outside of a loop"]);
+        assert_eq!(parse_errors("continue"), vec!["`continue` outside of a loop"]);
+        assert_eq!(parse_errors("return 1"), vec!["`return` outside of a function"]);
+        // A lambda can't leave a loop around it, but can return from itself.
+        assert_eq!(parse_errors("for ((1,)) |i| { f := || break }"), vec!["`break` outside of a loop"]);
+        assert_eq!(parse_errors("while true { f := || continue }"), vec!["`continue` outside of a loop"]);
+        assert_eq!(parse_errors("for ((1,)) |i| { f := || return i }"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn list_methods_with_closures() {
+        assert_eq!(run("(1, 2, 3).map |x| x * 2"), Ok(ints(&[2, 4, 6])));
+        assert_eq!(run("(1, 2, 3).map(|x| x * 2)"), Ok(ints(&[2, 4, 6])));
+        assert_eq!(run("xs := (1, 2, 3)\nxs.reduce(0) |a, b| a + b"), Ok(Obj::Int(6)));
+        assert_eq!(run("(1, 2, 3, 4).filter |x| x % 2 == 0"), Ok(ints(&[2, 4])));
+        assert_eq!(run("(3, 1, 2).sort_by |x| -x"), Ok(ints(&[3, 2, 1])));
+        assert_eq!(run("(1, 2, 3).find |x| x > 1"), Ok(Obj::Int(2)));
+        assert_eq!(run("(1, 2).any |x| x > 1"), Ok(Obj::Boolean(true)));
+        assert_eq!(run("(1, 2).all |x| x > 1"), Ok(Obj::Boolean(false)));
+        assert_eq!(run("(1, 2, 3).map((_ + 1)).join('-')"), Ok(Obj::String("2-3-4".into())));
+        assert_eq!(run("(1, 2).map(|x| x / 0)").unwrap_err().message(), "Division by zero");
+    }
+
+    #[test]
+    fn list_methods_mutate_in_place() {
+        assert_eq!(run("xs := (1,)\nxs.push(2)\nxs.insert(0, 0)\nxs"), Ok(ints(&[0, 1, 2])));
+        assert_eq!(run("xs := (1, 2, 3)\nxs.remove(-1) + xs.pop() + xs.len()"), Ok(Obj::Int(6)));
+        // A callback may change the list, since methods work on a snapshot of it.
+        assert_eq!(run("xs := (1, 2)\nxs.map |x| xs.push(x)\nxs"), Ok(ints(&[1, 2, 1, 2])));
+        assert!(run("xs := (1,)\nxs.insert(xs, 0)").is_err());
+    }
+
+    #[test]
+    fn iterator_state_in_captured_variables() {
+        let source = "i := 0\nit := ('next': || { i = i + 1; if i <= 3 then i })\nseen := (,)\nfor (it) |n| seen := seen + (n,)\nseen";
+        assert_eq!(run(source), Ok(ints(&[1, 2, 3])));
+    }
+
+    #[test]
+    fn iterator_state_in_the_map() {
+        let source = "it := ('n': 3, 'next': |it| if it['n'] > 0 then { it['n'] := it['n'] - 1; it['n'] + 1 })\nseen := (,)\nfor (it) |n| seen := seen + (n,)\nseen";
+        assert_eq!(run(source), Ok(ints(&[3, 2, 1])));
+    }
+
+    /// Matches `value` against a pattern parsed from a match arm, returning the bound variables.
+    fn bound(pattern: &str, value: Obj) -> Option<Vec<(String, Obj)>> {
+        let tokens = token::lex(&format!("match nil {{ {}: nil }}", pattern)).unwrap();
+        let (exprs, errors) = Parser::new(&tokens).parse_all();
+        assert_eq!(errors, Vec::new());
+        let Expr::Match(_, arms, _) = &exprs[0].node else { panic!("expected a match") };
+        let mut scope = HashMap::new();
+        if !bind(&arms[0].pattern, &value.as_ref(), &mut scope) {
+            return None;
+        }
+        let mut bound: Vec<_> = scope.into_iter().map(|(name, value)| (name, value.borrow().clone())).collect();
+        bound.sort_by(|a, b| a.0.cmp(&b.0));
+        Some(bound)
+    }
+
+    #[test]
+    fn bind_literals_and_wildcards() {
+        assert_eq!(bound("_", Obj::Nil), Some(vec![]));
+        assert_eq!(bound("x", Obj::Int(1)), Some(vec![("x".into(), Obj::Int(1))]));
+        assert_eq!(bound("-2", Obj::Int(-2)), Some(vec![]));
+        assert_eq!(bound("'a'", Obj::String("b".into())), None);
+        assert_eq!(bound("nil", Obj::Boolean(false)), None);
+    }
+
+    #[test]
+    fn bind_integers_and_numbers_alike() {
+        assert_eq!(bound("1", Obj::Number(1.0)), Some(vec![]));
+        assert_eq!(bound("1.0", Obj::Int(1)), Some(vec![]));
+        assert_eq!(bound("1.5", Obj::Int(1)), None);
+    }
+
+    #[test]
+    fn bind_lists() {
+        assert_eq!(bound("(a, b)", ints(&[1, 2])), Some(vec![("a".into(), Obj::Int(1)), ("b".into(), Obj::Int(2))]));
+        assert_eq!(bound("(a, b)", ints(&[1, 2, 3])), None);
+        assert_eq!(bound("(a, ..rest)", ints(&[1, 2, 3])), Some(vec![("a".into(), Obj::Int(1)), ("rest".into(), ints(&[2, 3]))]));
+        assert_eq!(bound("(a, ..rest)", ints(&[1])), Some(vec![("a".into(), Obj::Int(1)), ("rest".into(), ints(&[]))]));
+        assert_eq!(bound("(a, b, ..rest)", ints(&[1])), None);
+        assert_eq!(bound("(,)", ints(&[])), Some(vec![]));
+    }
+
+    #[test]
+    fn bind_maps() {
+        let map = Obj::Map([("k".to_string(), Obj::Int(1).as_ref()), ("j".to_string(), Obj::Nil.as_ref())].into_iter().collect());
+        assert_eq!(bound("('k': v)", map.clone()), Some(vec![("v".into(), Obj::Int(1))]));
+        assert_eq!(bound("('k': 2)", map.clone()), None);
+        assert_eq!(bound("('missing': _)", map), None);
+    }
+
+    #[test]
+    fn positions_count_back_from_the_end() {
+        assert_eq!(position(&Obj::Int(0), 3), Ok(0));
+        assert_eq!(position(&Obj::Int(-1), 3), Ok(2));
+        assert_eq!(position(&Obj::Int(-3), 3), Ok(0));
+        assert_eq!(position(&Obj::Int(3), 3), Err(Error::runtime("Index 3 out of bounds for length 3")));
+        assert_eq!(position(&Obj::Int(-4), 3), Err(Error::runtime("Index -4 out of bounds for length 3")));
+        assert_eq!(position(&Obj::Int(i64::MIN), 3), Err(Error::runtime(format!("Index {} out of bounds for length 3", i64::MIN))));
+        assert_eq!(position(&Obj::Nil, 3), Err(Error::runtime("Index must be an integer, not nil")));
+    }
+
+    #[test]
+    fn slice_bounds() {
+        assert_eq!(bounds(None, None, 4), Ok(0..4));
+        assert_eq!(bounds(Some(&Obj::Int(1)), None, 4), Ok(1..4));
+        assert_eq!(bounds(None, Some(&Obj::Int(-1)), 4), Ok(0..3));
+        assert_eq!(bounds(Some(&Obj::Int(-2)), Some(&Obj::Int(4)), 4), Ok(2..4));
+        assert_eq!(bounds(Some(&Obj::Int(2)), Some(&Obj::Int(2)), 4), Ok(2..2));
+        assert_eq!(bounds(Some(&Obj::Int(3)), Some(&Obj::Int(1)), 4), Err(Error::runtime("Slice 3..1 out of bounds for length 4")));
+        assert_eq!(bounds(None, Some(&Obj::Int(5)), 4), Err(Error::runtime("Slice ..5 out of bounds for length 4")));
+        assert_eq!(bounds(Some(&Obj::Number(1.5)), None, 4), Err(Error::runtime("Slice bounds must be integers, not 1.5")));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint_arithmetic() {
+        let big = |n: &str| Obj::BigInt(n.parse().unwrap());
+        assert_eq!(run("-7n / 2n"), Ok(big("-4")));
+        assert_eq!(run("-7n % 2n"), Ok(big("1")));
+        assert_eq!(run("7n % -2n"), Ok(big("-1")));
+        assert_eq!(run("9223372036854775807n * 2"), Ok(big("18446744073709551614")));
+        assert_eq!(run("1n + 0.5"), Ok(Obj::Number(1.5)));
+        assert_eq!(run("1n == 1"), Ok(Obj::Boolean(true)));
+        assert_eq!(run("7n / 0n").unwrap_err().message(), "Division by zero");
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn decimal_arithmetic() {
+        assert_eq!(run("1.10d + 1").map(|n| n.to_string()), Ok("2.10".to_string()));
+        assert_eq!(run("0.1d + 0.2d == 0.3d"), Ok(Obj::Boolean(true)));
+        assert_eq!(run("7d / 2"), Ok(Obj::Decimal("3.5".parse().unwrap())));
+        assert_eq!(run("1.10d + 1.5").unwrap_err().message(), "Invalid operands for +: 1.10 and 1.5");
+        assert_eq!(run("1.5 * 1.10d").unwrap_err().message(), "Invalid operands for *: 1.5 and 1.10");
+        assert_eq!(run("79228162514264337593543950335d + 1d").unwrap_err().message(), "Decimal overflow");
+        assert_eq!(run("1d / 0d").unwrap_err().message(), "Division by zero");
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint_conversions() {
+        let big = |n: &str| Obj::BigInt(n.parse().unwrap());
+        assert_eq!(run("bigint(3.9)"), Ok(big("3")));
+        assert_eq!(run("bigint(-3.9)"), Ok(big("-3")));
+        assert_eq!(run("bigint(' 12345678901234567890 ')"), Ok(big("12345678901234567890")));
+        assert_eq!(run("bigint('x')").unwrap_err().message(), "Cannot convert x to bigint");
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn decimal_conversions() {
+        assert_eq!(run("decimal('1.10')").map(|n| n.to_string()), Ok("1.10".to_string()));
+        assert_eq!(run("decimal(0.5)"), Ok(Obj::Decimal("0.5".parse().unwrap())));
+        assert_eq!(run("decimal(3)"), Ok(Obj::Decimal(3.into())));
+        assert_eq!(run("decimal(nil)").unwrap_err().message(), "Cannot convert nil to decimal");
+    }
+
+    #[cfg(all(feature = "bigint", feature = "decimal"))]
+    #[test]
+    fn conversions_between_bigint_and_decimal() {
+        assert_eq!(run("bigint(2.75d)"), Ok(Obj::BigInt(2.into())));
+        assert_eq!(run("decimal(12n)"), Ok(Obj::Decimal(12.into())));
+    }
+}
\ No newline at end of file
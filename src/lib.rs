@@ -0,0 +1,12 @@
+pub mod token;
+pub mod ast;
+pub mod parser;
+pub mod eval;
+pub mod obj;
+pub mod builtins;
+pub mod methods;
+pub mod profile;
+pub mod error;
+pub mod diagnostic;
+
+pub use error::Error;
@@ -1,13 +1,6 @@
-mod token;
-mod ast;
-mod parser;
-mod eval;
-mod obj;
-mod profile;
-
-use logos::Logos;
-use token::Token;
-use crate::token::SpannedToken;
+use std::io::IsTerminal;
+use bento::diagnostic::Renderer;
+use bento::{eval, parser, token, Error};
 
 const SOURCE: &str = r#"
 eval := |expr| {
@@ -24,19 +17,47 @@ eval := |expr| {
     } else stk.push(num(t))
   }
   stk.pop()
-}"#;
+}
+eval('1 2 +')
+eval('10 2.5 - 3 +')"#;
+
+fn run(source: &str, renderer: &Renderer) -> Result<(), Vec<Error>> {
+    let tokens = token::lex(source).map_err(|error| vec![error])?;
+    let mut parser = parser::Parser::new(tokens.as_slice());
+    let (exprs, errors) = parser.parse_all();
+    for warning in parser.take_warnings() {
+        eprint!("{}", renderer.render(&warning));
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut evaluator = eval::Evaluator::new();
+    for expr in &exprs {
+        let result = evaluator.eval(expr).map_err(|error| vec![error])?;
+        println!("{}", result.borrow());
+    }
+
+    Ok(())
+}
 
 fn main() {
-    let lex = Token::lexer(SOURCE);
-    let spanned_tokens: Vec<SpannedToken> = lex
-        .spanned()
-        .map(|(token, span)| match token {
-            Ok(t) => SpannedToken::new(t, span),
-            Err(_) => SpannedToken::new(Token::Error, span),
-        })
-        .collect();
+    let path = std::env::args().nth(1);
+    let source = match &path {
+        Some(path) => std::fs::read_to_string(path).unwrap_or_else(|error| {
+            eprintln!("error: cannot read {}: {}", path, error);
+            std::process::exit(1);
+        }),
+        None => SOURCE.to_string(),
+    };
 
-    let mut parser = parser::Parser::new(spanned_tokens.as_slice());
-    let expr = parser.parse();
-    println!("{:#?}", expr);
-}
\ No newline at end of file
+    let renderer = Renderer::new(&source)
+        .with_name(path.as_deref().unwrap_or("<source>"))
+        .with_color(std::io::stderr().is_terminal());
+    if let Err(errors) = run(&source, &renderer) {
+        for error in &errors {
+            eprint!("{}", renderer.render_error(error));
+        }
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,461 @@
+use crate::error::{Error, Result};
+use crate::eval;
+use crate::obj::{Obj, ObjRef};
+use crate::token::Token;
+
+/// Runs a closure or builtin passed to a method, like `f` in `xs.map(f)`.
+pub type Call<'a> = dyn FnMut(&ObjRef, Vec<ObjRef>) -> Result<ObjRef> + 'a;
+
+pub type MethodFn = fn(&ObjRef, &[ObjRef], &mut Call) -> Result<ObjRef>;
+
+/// List methods. `push`, `pop`, `insert` and `remove` change the list in place; the rest leave it alone
+/// and return a new value.
+const LIST: &[(&str, MethodFn)] = &[
+    ("push", push),
+    ("pop", pop),
+    ("insert", insert),
+    ("remove", remove),
+    ("len", len),
+    ("has", has),
+    ("map", map),
+    ("filter", filter),
+    ("reduce", reduce),
+    ("sort", sort),
+    ("sort_by", sort_by),
+    ("reverse", reverse),
+    ("find", find),
+    ("any", any),
+    ("all", all),
+    ("zip", zip),
+    ("enumerate", enumerate),
+    ("join", join),
+    ("chunks", chunks),
+    ("windows", windows),
+];
+
+const MAP: &[(&str, MethodFn)] = &[("len", len), ("has", has)];
+
+const RANGE: &[(&str, MethodFn)] = &[("len", len), ("has", has)];
+
+/// Finds the native method `name` of `target`'s type, which `target.name(args)` calls when `target`
+/// isn't a map with its own `name` entry.
+pub fn lookup(target: &Obj, name: &str) -> Option<MethodFn> {
+    let methods = match target {
+        Obj::List(_) => LIST,
+        Obj::Map(_) => MAP,
+        Obj::Range(_) => RANGE,
+        _ => return None,
+    };
+    methods.iter().find(|&&(method, _)| method == name).map(|&(_, func)| func)
+}
+
+fn arity<'a, const N: usize>(name: &str, args: &'a [ObjRef]) -> Result<&'a [ObjRef; N]> {
+    args.try_into().map_err(|_| {
+        let plural = if N == 1 { "" } else { "s" };
+        Error::runtime(format!("{} expects {} argument{}, got {}", name, N, plural, args.len()))
+    })
+}
+
+/// A snapshot of the list's elements, so callbacks are free to change the list while a method runs.
+fn items(list: &ObjRef) -> Vec<ObjRef> {
+    match &*list.borrow() {
+        Obj::List(items) => items.clone(),
+        _ => unreachable!("list methods are only looked up for lists"),
+    }
+}
+
+fn with_items<T>(list: &ObjRef, f: impl FnOnce(&mut Vec<ObjRef>) -> T) -> T {
+    match &mut *list.borrow_mut() {
+        Obj::List(items) => f(items),
+        _ => unreachable!("list methods are only looked up for lists"),
+    }
+}
+
+fn count(name: &str, arg: &ObjRef) -> Result<usize> {
+    match *arg.borrow() {
+        Obj::Int(n) if n > 0 => Ok(n as usize),
+        ref obj => Err(Error::runtime(format!("{} expects a positive integer, not {}", name, obj))),
+    }
+}
+
+fn list(items: Vec<ObjRef>) -> Result<ObjRef> {
+    Ok(Obj::List(items).as_ref())
+}
+
+fn push(target: &ObjRef, args: &[ObjRef], _: &mut Call) -> Result<ObjRef> {
+    let [value] = arity("push", args)?;
+    with_items(target, |items| items.push(value.clone()));
+    Ok(Obj::Nil.as_ref())
+}
+
+fn pop(target: &ObjRef, args: &[ObjRef], _: &mut Call) -> Result<ObjRef> {
+    let [] = arity("pop", args)?;
+    with_items(target, |items| items.pop()).ok_or_else(|| Error::runtime("Cannot pop from an empty list"))
+}
+
+/// Inserts before the element at the index, which may also be the length to append.
+fn insert(target: &ObjRef, args: &[ObjRef], _: &mut Call) -> Result<ObjRef> {
+    let [index, value] = arity("insert", args)?;
+    // The index is resolved before borrowing the list mutably, since it may be the list itself.
+    let len = items(target).len();
+    let index = match *index.borrow() {
+        Obj::Int(i) if i == len as i64 => len,
+        ref index => eval::position(index, len)?,
+    };
+    with_items(target, |items| items.insert(index, value.clone()));
+    Ok(Obj::Nil.as_ref())
+}
+
+fn remove(target: &ObjRef, args: &[ObjRef], _: &mut Call) -> Result<ObjRef> {
+    let [index] = arity("remove", args)?;
+    let index = eval::position(&index.borrow(), items(target).len())?;
+    Ok(with_items(target, |items| items.remove(index)))
+}
+
+fn len(target: &ObjRef, args: &[ObjRef], _: &mut Call) -> Result<ObjRef> {
+    let [] = arity("len", args)?;
+    let len = match &*target.borrow() {
+        Obj::List(items) => items.len(),
+        Obj::Map(map) => map.len(),
+        Obj::Range(range) => range.len(),
+        _ => unreachable!("len is only looked up for collections"),
+    };
+    Ok(Obj::Int(i64::try_from(len).unwrap_or(i64::MAX)).as_ref())
+}
+
+/// Whether a list holds an equal element, a map has the key or a range includes the integer.
+fn has(target: &ObjRef, args: &[ObjRef], _: &mut Call) -> Result<ObjRef> {
+    let [value] = arity("has", args)?;
+    let value = &*value.borrow();
+    let found = match (&*target.borrow(), value) {
+        (Obj::List(items), value) => items.iter().any(|item| *item.borrow() == *value),
+        (Obj::Map(map), Obj::String(key)) => map.contains_key(key),
+        (Obj::Range(range), Obj::Int(n)) => range.contains(*n),
+        _ => false,
+    };
+    Ok(Obj::Boolean(found).as_ref())
+}
+
+fn map(target: &ObjRef, args: &[ObjRef], call: &mut Call) -> Result<ObjRef> {
+    let [f] = arity("map", args)?;
+    list(items(target).into_iter().map(|item| call(f, vec![item])).collect::<Result<_>>()?)
+}
+
+fn filter(target: &ObjRef, args: &[ObjRef], call: &mut Call) -> Result<ObjRef> {
+    let [f] = arity("filter", args)?;
+    let mut kept = Vec::new();
+    for item in items(target) {
+        if call(f, vec![item.clone()])?.borrow().is_truthy() {
+            kept.push(item);
+        }
+    }
+    list(kept)
+}
+
+/// Folds the elements with `f(accumulator, element)`, starting from the initial value if one comes
+/// before `f`, as in `xs.reduce(0) |sum, x| sum + x`, or from the first element otherwise.
+fn reduce(target: &ObjRef, args: &[ObjRef], call: &mut Call) -> Result<ObjRef> {
+    let mut items = items(target).into_iter();
+    let (mut acc, f) = match args {
+        [init, f] => (init.clone(), f),
+        [f] => (items.next().ok_or_else(|| Error::runtime("Cannot reduce an empty list without an initial value"))?, f),
+        _ => return Err(Error::runtime(format!("reduce expects 1 or 2 arguments, got {}", args.len()))),
+    };
+    for item in items {
+        acc = call(f, vec![acc, item])?;
+    }
+    Ok(acc)
+}
+
+fn sort(target: &ObjRef, args: &[ObjRef], _: &mut Call) -> Result<ObjRef> {
+    let [] = arity("sort", args)?;
+    let keyed = items(target).into_iter().map(|item| (item.clone(), item)).collect();
+    list(merge_sort(keyed)?.into_iter().map(|(_, item)| item).collect())
+}
+
+/// Sorts by the keys `f` returns for each element, calling it once per element.
+fn sort_by(target: &ObjRef, args: &[ObjRef], call: &mut Call) -> Result<ObjRef> {
+    let [f] = arity("sort_by", args)?;
+    let keyed = items(target)
+        .into_iter()
+        .map(|item| Ok((call(f, vec![item.clone()])?, item)))
+        .collect::<Result<_>>()?;
+    list(merge_sort(keyed)?.into_iter().map(|(_, item)| item).collect())
+}
+
+/// A stable sort on the keys, ordered by `<`. Comparing script values can fail, or be inconsistent as
+/// with NaN, which rules out the standard library's sorts.
+fn merge_sort(mut keyed: Vec<(ObjRef, ObjRef)>) -> Result<Vec<(ObjRef, ObjRef)>> {
+    if keyed.len() < 2 {
+        return Ok(keyed);
+    }
+    let right = keyed.split_off(keyed.len() / 2);
+    let (left, right) = (merge_sort(keyed)?, merge_sort(right)?);
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+    while let (Some((a, _)), Some((b, _))) = (left.peek(), right.peek()) {
+        let before = eval::binary(&b.borrow(), &Token::LessThan, &a.borrow())?.is_truthy();
+        merged.extend(if before { right.next() } else { left.next() });
+    }
+    merged.extend(left.chain(right));
+    Ok(merged)
+}
+
+fn reverse(target: &ObjRef, args: &[ObjRef], _: &mut Call) -> Result<ObjRef> {
+    let [] = arity("reverse", args)?;
+    list(items(target).into_iter().rev().collect())
+}
+
+/// The first element `f` accepts, or nil.
+fn find(target: &ObjRef, args: &[ObjRef], call: &mut Call) -> Result<ObjRef> {
+    let [f] = arity("find", args)?;
+    for item in items(target) {
+        if call(f, vec![item.clone()])?.borrow().is_truthy() {
+            return Ok(item);
+        }
+    }
+    Ok(Obj::Nil.as_ref())
+}
+
+fn any(target: &ObjRef, args: &[ObjRef], call: &mut Call) -> Result<ObjRef> {
+    let [f] = arity("any", args)?;
+    for item in items(target) {
+        if call(f, vec![item])?.borrow().is_truthy() {
+            return Ok(Obj::Boolean(true).as_ref());
+        }
+    }
+    Ok(Obj::Boolean(false).as_ref())
+}
+
+fn all(target: &ObjRef, args: &[ObjRef], call: &mut Call) -> Result<ObjRef> {
+    let [f] = arity("all", args)?;
+    for item in items(target) {
+        if !call(f, vec![item])?.borrow().is_truthy() {
+            return Ok(Obj::Boolean(false).as_ref());
+        }
+    }
+    Ok(Obj::Boolean(true).as_ref())
+}
+
+/// Pairs up elements with another list's, stopping at the end of the shorter one.
+fn zip(target: &ObjRef, args: &[ObjRef], _: &mut Call) -> Result<ObjRef> {
+    let [other] = arity("zip", args)?;
+    let other = match &*other.borrow() {
+        Obj::List(other) => other.clone(),
+        obj => return Err(Error::runtime(format!("Cannot zip with {}", obj))),
+    };
+    list(items(target).into_iter().zip(other).map(|(a, b)| Obj::List(vec![a, b]).as_ref()).collect())
+}
+
+fn enumerate(target: &ObjRef, args: &[ObjRef], _: &mut Call) -> Result<ObjRef> {
+    let [] = arity("enumerate", args)?;
+    let pairs = items(target).into_iter().enumerate();
+    list(pairs.map(|(i, item)| Obj::List(vec![Obj::Int(i as i64).as_ref(), item]).as_ref()).collect())
+}
+
+/// Joins the elements' text with the separator, or with nothing when it's left out.
+fn join(target: &ObjRef, args: &[ObjRef], _: &mut Call) -> Result<ObjRef> {
+    let separator = match args {
+        [] => String::new(),
+        [separator] => separator.borrow().to_string(),
+        _ => return Err(Error::runtime(format!("join expects 0 or 1 arguments, got {}", args.len()))),
+    };
+    let text: Vec<String> = items(target).iter().map(|item| item.borrow().to_string()).collect();
+    Ok(Obj::String(text.join(&separator)).as_ref())
+}
+
+/// Splits into lists of `n` elements, the last of which may be shorter.
+fn chunks(target: &ObjRef, args: &[ObjRef], _: &mut Call) -> Result<ObjRef> {
+    let [n] = arity("chunks", args)?;
+    let n = count("chunks", n)?;
+    list(items(target).chunks(n).map(|chunk| Obj::List(chunk.to_vec()).as_ref()).collect())
+}
+
+/// Every run of `n` consecutive elements, overlapping.
+fn windows(target: &ObjRef, args: &[ObjRef], _: &mut Call) -> Result<ObjRef> {
+    let [n] = arity("windows", args)?;
+    let n = count("windows", n)?;
+    list(items(target).windows(n).map(|window| Obj::List(window.to_vec()).as_ref()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ints(values: &[i64]) -> ObjRef {
+        Obj::List(values.iter().map(|&n| Obj::Int(n).as_ref()).collect()).as_ref()
+    }
+
+    /// Calls the list method `name`. Callbacks are given by name and run natively, so the methods are
+    /// tested apart from the evaluator.
+    fn call(target: &ObjRef, name: &str, args: &[ObjRef]) -> Result<ObjRef> {
+        let method = lookup(&target.borrow(), name).unwrap();
+        method(target, args, &mut |f, args| {
+            let int = |i: usize| match *args[i].borrow() {
+                Obj::Int(n) => n,
+                ref obj => panic!("expected an integer, got {}", obj),
+            };
+            let result = match (&*f.borrow(), args.len()) {
+                (Obj::String(f), 1) if f == "double" => Obj::Int(int(0) * 2),
+                (Obj::String(f), 1) if f == "even" => Obj::Boolean(int(0) % 2 == 0),
+                (Obj::String(f), 1) if f == "negate" => Obj::Int(-int(0)),
+                (Obj::String(f), 1) if f == "parity" => Obj::Int(int(0) % 2),
+                (Obj::String(f), 1) if f == "fail" => return Err(Error::runtime("failed")),
+                (Obj::String(f), 2) if f == "add" => Obj::Int(int(0) + int(1)),
+                (f, _) => panic!("unknown callback {}", f),
+            };
+            Ok(result.as_ref())
+        })
+    }
+
+    fn callback(name: &str) -> ObjRef {
+        Obj::String(name.into()).as_ref()
+    }
+
+    fn int(n: i64) -> ObjRef {
+        Obj::Int(n).as_ref()
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let xs = ints(&[1]);
+        assert_eq!(*call(&xs, "push", &[int(2)]).unwrap().borrow(), Obj::Nil);
+        assert_eq!(*xs.borrow(), *ints(&[1, 2]).borrow());
+        assert_eq!(*call(&xs, "pop", &[]).unwrap().borrow(), Obj::Int(2));
+        call(&xs, "pop", &[]).unwrap();
+        assert_eq!(call(&xs, "pop", &[]).unwrap_err(), Error::runtime("Cannot pop from an empty list"));
+        assert_eq!(call(&xs, "push", &[]).unwrap_err(), Error::runtime("push expects 1 argument, got 0"));
+    }
+
+    #[test]
+    fn insert() {
+        let xs = ints(&[1, 3]);
+        call(&xs, "insert", &[int(1), int(2)]).unwrap();
+        call(&xs, "insert", &[int(3), int(4)]).unwrap();
+        call(&xs, "insert", &[int(-4), int(0)]).unwrap();
+        assert_eq!(*xs.borrow(), *ints(&[0, 1, 2, 3, 4]).borrow());
+        assert_eq!(call(&xs, "insert", &[int(6), int(0)]).unwrap_err(), Error::runtime("Index 6 out of bounds for length 5"));
+        assert_eq!(call(&xs, "insert", &[xs.clone(), int(0)]).unwrap_err(), Error::runtime("Index must be an integer, not (0, 1, 2, 3, 4)"));
+    }
+
+    #[test]
+    fn remove() {
+        let xs = ints(&[1, 2, 3]);
+        assert_eq!(*call(&xs, "remove", &[int(-1)]).unwrap().borrow(), Obj::Int(3));
+        assert_eq!(*call(&xs, "remove", &[int(0)]).unwrap().borrow(), Obj::Int(1));
+        assert_eq!(*xs.borrow(), *ints(&[2]).borrow());
+        assert_eq!(call(&xs, "remove", &[int(1)]).unwrap_err(), Error::runtime("Index 1 out of bounds for length 1"));
+        assert!(call(&xs, "remove", std::slice::from_ref(&xs)).is_err());
+    }
+
+    #[test]
+    fn len_and_has() {
+        let xs = ints(&[1, 2]);
+        assert_eq!(*call(&xs, "len", &[]).unwrap().borrow(), Obj::Int(2));
+        assert_eq!(*call(&xs, "has", &[int(2)]).unwrap().borrow(), Obj::Boolean(true));
+        assert_eq!(*call(&xs, "has", &[Obj::Number(2.0).as_ref()]).unwrap().borrow(), Obj::Boolean(true));
+        assert_eq!(*call(&xs, "has", &[int(3)]).unwrap().borrow(), Obj::Boolean(false));
+    }
+
+    #[test]
+    fn map() {
+        let xs = ints(&[1, 2, 3]);
+        assert_eq!(*call(&xs, "map", &[callback("double")]).unwrap().borrow(), *ints(&[2, 4, 6]).borrow());
+        assert_eq!(*xs.borrow(), *ints(&[1, 2, 3]).borrow());
+        assert_eq!(call(&xs, "map", &[callback("fail")]).unwrap_err(), Error::runtime("failed"));
+    }
+
+    #[test]
+    fn filter() {
+        let xs = ints(&[1, 2, 3, 4]);
+        assert_eq!(*call(&xs, "filter", &[callback("even")]).unwrap().borrow(), *ints(&[2, 4]).borrow());
+    }
+
+    #[test]
+    fn reduce() {
+        let xs = ints(&[1, 2, 3]);
+        assert_eq!(*call(&xs, "reduce", &[callback("add")]).unwrap().borrow(), Obj::Int(6));
+        assert_eq!(*call(&xs, "reduce", &[int(10), callback("add")]).unwrap().borrow(), Obj::Int(16));
+        assert_eq!(*call(&ints(&[]), "reduce", &[int(10), callback("add")]).unwrap().borrow(), Obj::Int(10));
+        let error = Error::runtime("Cannot reduce an empty list without an initial value");
+        assert_eq!(call(&ints(&[]), "reduce", &[callback("add")]).unwrap_err(), error);
+    }
+
+    #[test]
+    fn sort() {
+        let xs = ints(&[3, 1, 2]);
+        assert_eq!(*call(&xs, "sort", &[]).unwrap().borrow(), *ints(&[1, 2, 3]).borrow());
+        assert_eq!(*xs.borrow(), *ints(&[3, 1, 2]).borrow());
+        let mixed = Obj::List(vec![int(1), Obj::String("a".into()).as_ref()]).as_ref();
+        assert!(call(&mixed, "sort", &[]).is_err());
+    }
+
+    #[test]
+    fn sort_by() {
+        let xs = ints(&[1, 3, 2]);
+        assert_eq!(*call(&xs, "sort_by", &[callback("negate")]).unwrap().borrow(), *ints(&[3, 2, 1]).borrow());
+    }
+
+    #[test]
+    fn sort_by_is_stable() {
+        let xs = ints(&[4, 1, 2, 3]);
+        assert_eq!(*call(&xs, "sort_by", &[callback("parity")]).unwrap().borrow(), *ints(&[4, 2, 1, 3]).borrow());
+    }
+
+    #[test]
+    fn reverse() {
+        assert_eq!(*call(&ints(&[1, 2, 3]), "reverse", &[]).unwrap().borrow(), *ints(&[3, 2, 1]).borrow());
+    }
+
+    #[test]
+    fn find() {
+        let xs = ints(&[1, 2, 3, 4]);
+        assert_eq!(*call(&xs, "find", &[callback("even")]).unwrap().borrow(), Obj::Int(2));
+        assert_eq!(*call(&ints(&[1, 3]), "find", &[callback("even")]).unwrap().borrow(), Obj::Nil);
+    }
+
+    #[test]
+    fn any_and_all() {
+        let xs = ints(&[1, 2]);
+        assert_eq!(*call(&xs, "any", &[callback("even")]).unwrap().borrow(), Obj::Boolean(true));
+        assert_eq!(*call(&xs, "all", &[callback("even")]).unwrap().borrow(), Obj::Boolean(false));
+        assert_eq!(*call(&ints(&[]), "any", &[callback("fail")]).unwrap().borrow(), Obj::Boolean(false));
+        assert_eq!(*call(&ints(&[]), "all", &[callback("fail")]).unwrap().borrow(), Obj::Boolean(true));
+    }
+
+    #[test]
+    fn zip() {
+        let pairs = call(&ints(&[1, 2, 3]), "zip", &[ints(&[4, 5])]).unwrap();
+        assert_eq!(*pairs.borrow(), Obj::List(vec![ints(&[1, 4]), ints(&[2, 5])]));
+        assert_eq!(call(&ints(&[1]), "zip", &[int(1)]).unwrap_err(), Error::runtime("Cannot zip with 1"));
+    }
+
+    #[test]
+    fn enumerate() {
+        let pairs = call(&ints(&[5, 6]), "enumerate", &[]).unwrap();
+        assert_eq!(*pairs.borrow(), Obj::List(vec![ints(&[0, 5]), ints(&[1, 6])]));
+    }
+
+    #[test]
+    fn join() {
+        let xs = ints(&[1, 2, 3]);
+        assert_eq!(*call(&xs, "join", &[]).unwrap().borrow(), Obj::String("123".into()));
+        assert_eq!(*call(&xs, "join", &[Obj::String(", ".into()).as_ref()]).unwrap().borrow(), Obj::String("1, 2, 3".into()));
+    }
+
+    #[test]
+    fn chunks() {
+        let chunks = call(&ints(&[1, 2, 3]), "chunks", &[int(2)]).unwrap();
+        assert_eq!(*chunks.borrow(), Obj::List(vec![ints(&[1, 2]), ints(&[3])]));
+        assert_eq!(call(&ints(&[1]), "chunks", &[int(0)]).unwrap_err(), Error::runtime("chunks expects a positive integer, not 0"));
+    }
+
+    #[test]
+    fn windows() {
+        let windows = call(&ints(&[1, 2, 3]), "windows", &[int(2)]).unwrap();
+        assert_eq!(*windows.borrow(), Obj::List(vec![ints(&[1, 2]), ints(&[2, 3])]));
+        assert_eq!(*call(&ints(&[1]), "windows", &[int(2)]).unwrap().borrow(), Obj::List(vec![]));
+        assert!(call(&ints(&[1]), "windows", &[int(-1)]).is_err());
+    }
+}
\ No newline at end of file
@@ -1,40 +1,75 @@
 use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
 use std::hash::{Hash, Hasher};
-use std::iter::{FlatMap, Map};
 use std::rc::Rc;
-use crate::ast::Expr;
+use crate::ast::SpannedExpr;
+use crate::error::{Error, Result};
 
-#[derive(Debug, Clone)]
+/// A native function, called with its evaluated arguments.
+pub type BuiltinFn = fn(&[ObjRef]) -> Result<ObjRef>;
+
+#[derive(Clone)]
 pub enum Obj {
     Number(f64),
+    Int(i64),
+    #[cfg(feature = "bigint")]
+    BigInt(num_bigint::BigInt),
+    #[cfg(feature = "decimal")]
+    Decimal(rust_decimal::Decimal),
     String(String),
     Boolean(bool),
     Nil,
     List(Vec<ObjRef>),
-    Map(Map<String, ObjRef>),
+    Map(BTreeMap<String, ObjRef>),
+    Range(Range),
     Closure {
         params: Vec<String>,
-        body: Expr,
-        env: Vec<HashMap<String, ObjRef>>,
+        body: SpannedExpr,
+        env: Vec<Scope>,
+    },
+    Builtin {
+        name: &'static str,
+        func: BuiltinFn,
     }
 }
 
 pub type ObjRef = Rc<RefCell<Obj>>;
 
+/// A frame of variables. Closures share the frames they were created in rather than copying them, so
+/// they see later assignments, including their own binding when they recurse.
+pub type Scope = Rc<RefCell<HashMap<String, ObjRef>>>;
+
+/// A lazy integer range `start, start + step, ...` that stops before reaching `end`, or after reaching
+/// it when `inclusive`.
+#[derive(Debug, Clone, Copy)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub inclusive: bool,
+    pub step: i64,
+}
+
 impl Hash for Obj {
     fn hash<H: Hasher>(&self, state: &mut H) {
         match self {
             Obj::Number(n) => n.to_string().hash(state),
+            Obj::Int(n) => n.to_string().hash(state),
+            #[cfg(feature = "bigint")]
+            Obj::BigInt(n) => n.to_string().hash(state),
+            #[cfg(feature = "decimal")]
+            Obj::Decimal(n) => n.normalize().to_string().hash(state),
             Obj::String(s) => s.hash(state),
             Obj::Boolean(b) => b.to_string().hash(state),
             Obj::Nil => "nil".hash(state),
             Obj::List(l) => format!("{:?}", l).hash(state), // This is a hack, we should implement a better hash function
             Obj::Map(m) => format!("{:?}", m).hash(state),
+            Obj::Range(r) => (r.len(), r.get(0), (r.len() > 1).then_some(r.step)).hash(state),
             Obj::Closure { .. } => {
                 //TODO: Implement a better hash function
                 "closure".hash(state);
             }
+            Obj::Builtin { name, .. } => name.hash(state),
         }
     }
 }
@@ -43,12 +78,24 @@ impl PartialEq for Obj {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Obj::Number(a), Obj::Number(b)) => a == b,
+            (Obj::Int(a), Obj::Int(b)) => a == b,
+            (Obj::Int(a), Obj::Number(b)) | (Obj::Number(b), Obj::Int(a)) => *a as f64 == *b,
+            #[cfg(feature = "bigint")]
+            (Obj::BigInt(a), Obj::BigInt(b)) => a == b,
+            #[cfg(feature = "bigint")]
+            (Obj::BigInt(a), Obj::Int(b)) | (Obj::Int(b), Obj::BigInt(a)) => *a == num_bigint::BigInt::from(*b),
+            #[cfg(feature = "decimal")]
+            (Obj::Decimal(a), Obj::Decimal(b)) => a == b,
+            #[cfg(feature = "decimal")]
+            (Obj::Decimal(a), Obj::Int(b)) | (Obj::Int(b), Obj::Decimal(a)) => *a == rust_decimal::Decimal::from(*b),
             (Obj::String(a), Obj::String(b)) => a == b,
             (Obj::Boolean(a), Obj::Boolean(b)) => a == b,
             (Obj::Nil, Obj::Nil) => true,
             (Obj::List(a), Obj::List(b)) => a == b,
             (Obj::Map(a), Obj::Map(b)) => a.iter().eq(b),
+            (Obj::Range(a), Obj::Range(b)) => a == b,
             (Obj::Closure {..}, Obj::Closure{..}) => false,
+            (Obj::Builtin { name: a, .. }, Obj::Builtin { name: b, .. }) => a == b,
             _ => false
         }
     }
@@ -60,28 +107,279 @@ impl Obj {
     pub fn is_truthy(&self) -> bool {
         match self {
             Obj::Number(n) => *n != 0.0,
+            Obj::Int(n) => *n != 0,
+            #[cfg(feature = "bigint")]
+            Obj::BigInt(n) => n.sign() != num_bigint::Sign::NoSign,
+            #[cfg(feature = "decimal")]
+            Obj::Decimal(n) => !n.is_zero(),
             Obj::String(s) => !s.is_empty(),
             Obj::Boolean(b) => *b,
             Obj::Nil => false,
             Obj::List(l) => !l.is_empty(),
             Obj::Map(m) => !m.is_empty(),
-            Obj::Closure {..} => true
+            Obj::Range(r) => !r.is_empty(),
+            Obj::Closure {..} | Obj::Builtin {..} => true
         }
     }
-    
-    pub fn to_string(&self) -> String {
+
+    /// Numeric value of an `Int`, `BigInt` or `Number`, promoting integers to floating point. Decimals
+    /// have none, so they never silently mix with floats.
+    pub fn as_number(&self) -> Option<f64> {
         match self {
-            Obj::Number(n) => n.to_string(),
-            Obj::String(s) => s.clone(),
-            Obj::Boolean(b) => b.to_string(),
-            Obj::Nil => "nil".to_string(),
-            Obj::List(l) => format!("{:?}", l),
-            Obj::Map(m) => format!("{:?}", m),
-            Obj::Closure {..} => "lambda".to_string()
+            Obj::Number(n) => Some(*n),
+            Obj::Int(n) => Some(*n as f64),
+            #[cfg(feature = "bigint")]
+            Obj::BigInt(n) => num_traits::ToPrimitive::to_f64(n),
+            _ => None,
         }
     }
-    
+
+    pub fn is_numeric(&self) -> bool {
+        #[cfg(feature = "decimal")]
+        if matches!(self, Obj::Decimal(_)) {
+            return true;
+        }
+        self.as_number().is_some()
+    }
+
     pub fn as_ref(&self) -> ObjRef {
-        return Rc::new(RefCell::new(self.clone()))
+        Rc::new(RefCell::new(self.clone()))
+    }
+}
+
+impl std::fmt::Debug for Obj {
+    /// Like a derived `Debug`, except that closures leave out their environment, which usually holds
+    /// the closure itself.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Obj::Number(n) => f.debug_tuple("Number").field(n).finish(),
+            Obj::Int(n) => f.debug_tuple("Int").field(n).finish(),
+            #[cfg(feature = "bigint")]
+            Obj::BigInt(n) => f.debug_tuple("BigInt").field(n).finish(),
+            #[cfg(feature = "decimal")]
+            Obj::Decimal(n) => f.debug_tuple("Decimal").field(n).finish(),
+            Obj::String(s) => f.debug_tuple("String").field(s).finish(),
+            Obj::Boolean(b) => f.debug_tuple("Boolean").field(b).finish(),
+            Obj::Nil => write!(f, "Nil"),
+            Obj::List(l) => nested(self, f, |f| f.debug_tuple("List").field(l).finish()),
+            Obj::Map(m) => nested(self, f, |f| f.debug_tuple("Map").field(m).finish()),
+            Obj::Range(r) => f.debug_tuple("Range").field(r).finish(),
+            Obj::Closure { params, body, .. } => {
+                f.debug_struct("Closure").field("params", params).field("body", body).finish_non_exhaustive()
+            }
+            Obj::Builtin { name, .. } => f.debug_struct("Builtin").field("name", name).finish_non_exhaustive(),
+        }
+    }
+}
+
+impl Display for Obj {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Obj::Number(n) => write!(f, "{}", n),
+            Obj::Int(n) => write!(f, "{}", n),
+            #[cfg(feature = "bigint")]
+            Obj::BigInt(n) => write!(f, "{}", n),
+            #[cfg(feature = "decimal")]
+            Obj::Decimal(n) => write!(f, "{}", n),
+            Obj::String(s) => write!(f, "{}", s),
+            Obj::Boolean(b) => write!(f, "{}", b),
+            Obj::Nil => write!(f, "nil"),
+            Obj::List(l) if l.is_empty() => write!(f, "(,)"),
+            Obj::List(l) => nested(self, f, |f| {
+                write!(f, "(")?;
+                for (i, item) in l.iter().enumerate() {
+                    write!(f, "{}{}", if i > 0 { ", " } else { "" }, item.borrow())?;
+                }
+                write!(f, "{})", if l.len() == 1 { "," } else { "" })
+            }),
+            Obj::Map(m) if m.is_empty() => write!(f, "(:)"),
+            Obj::Map(m) => nested(self, f, |f| {
+                write!(f, "(")?;
+                for (i, (key, value)) in m.iter().enumerate() {
+                    write!(f, "{}'{}': {}", if i > 0 { ", " } else { "" }, key, value.borrow())?;
+                }
+                write!(f, ")")
+            }),
+            Obj::Range(r) => {
+                write!(f, "{}{}{}", r.start, if r.inclusive { "..=" } else { ".." }, r.end)?;
+                if r.step != 1 {
+                    write!(f, " by {}", r.step)?;
+                }
+                Ok(())
+            }
+            Obj::Closure {..} => write!(f, "lambda"),
+            Obj::Builtin { name, .. } => write!(f, "builtin {}", name)
+        }
+    }
+}
+
+thread_local! {
+    /// The lists and maps being printed, innermost last.
+    static PRINTING: RefCell<Vec<*const Obj>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Prints a list or map with `print`, or `(...)` if it is already being printed further out, as when
+/// `xs.push(xs)` makes a list contain itself.
+fn nested(obj: &Obj, f: &mut std::fmt::Formatter<'_>, print: impl FnOnce(&mut std::fmt::Formatter<'_>) -> std::fmt::Result) -> std::fmt::Result {
+    let obj = obj as *const Obj;
+    if PRINTING.with(|printing| printing.borrow().contains(&obj)) {
+        return write!(f, "(...)");
+    }
+    PRINTING.with(|printing| printing.borrow_mut().push(obj));
+    let result = crate::profile::grow(|| print(f));
+    PRINTING.with(|printing| printing.borrow_mut().pop());
+    result
+}
+
+impl Range {
+    /// Builds `start..end`, or `start..=end` when `inclusive`, counting by `step`.
+    pub fn new(start: i64, end: i64, inclusive: bool, step: i64) -> Result<Self> {
+        if step == 0 {
+            return Err(Error::runtime("Range step cannot be zero"));
+        }
+        Ok(Self { start, end, inclusive, step })
+    }
+
+    /// The first value past the range, which may lie outside `i64` for an inclusive range.
+    fn limit(&self) -> i128 {
+        self.end as i128 + if self.inclusive { self.step.signum() as i128 } else { 0 }
+    }
+
+    /// The number of elements, saturating for a range over nearly all of `i64`.
+    pub fn len(&self) -> usize {
+        let distance = if self.step > 0 {
+            self.limit() - self.start as i128
+        } else {
+            self.start as i128 - self.limit()
+        };
+        let step = self.step.unsigned_abs() as i128;
+        if distance <= 0 { 0 } else { ((distance + step - 1) / step).min(usize::MAX as i128) as usize }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<i64> {
+        (index < self.len()).then(|| (self.start as i128 + index as i128 * self.step as i128) as i64)
+    }
+
+    pub fn contains(&self, n: i64) -> bool {
+        let (n, start) = (n as i128, self.start as i128);
+        let in_bounds = if self.step > 0 { n >= start && n < self.limit() } else { n <= start && n > self.limit() };
+        in_bounds && (n - start) % self.step as i128 == 0
+    }
+
+    /// The sub-range covering the elements at `indices`, which must be in bounds.
+    pub fn slice(&self, indices: std::ops::Range<usize>) -> Self {
+        let at = |index: usize| (self.start as i128 + index as i128 * self.step as i128) as i64;
+        let start = at(indices.start);
+        if indices.end == self.len() {
+            Self { start, ..*self }
+        } else {
+            Self { start, end: at(indices.end), inclusive: false, step: self.step }
+        }
+    }
+}
+
+impl PartialEq for Range {
+    /// Ranges are equal when they produce the same elements.
+    fn eq(&self, other: &Self) -> bool {
+        let len = self.len();
+        len == other.len() && (len == 0 || self.start == other.start) && (len < 2 || self.step == other.step)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: i64, end: i64, inclusive: bool, step: i64) -> Range {
+        Range::new(start, end, inclusive, step).unwrap()
+    }
+
+    fn elements(range: &Range) -> Vec<i64> {
+        (0..range.len()).map(|i| range.get(i).unwrap()).collect()
+    }
+
+    #[test]
+    fn zero_step_is_an_error() {
+        assert_eq!(Range::new(0, 5, false, 0), Err(Error::runtime("Range step cannot be zero")));
+    }
+
+    #[test]
+    fn elements_and_length() {
+        assert_eq!(elements(&range(0, 5, false, 1)), vec![0, 1, 2, 3, 4]);
+        assert_eq!(elements(&range(0, 5, true, 2)), vec![0, 2, 4]);
+        assert_eq!(elements(&range(0, 6, true, 2)), vec![0, 2, 4, 6]);
+        assert_eq!(elements(&range(5, 0, false, -2)), vec![5, 3, 1]);
+        assert_eq!(elements(&range(5, 1, true, -2)), vec![5, 3, 1]);
+        assert!(range(3, 3, false, 1).is_empty());
+        assert!(range(0, 5, false, -1).is_empty());
+        assert_eq!(range(0, 5, false, 1).get(5), None);
+    }
+
+    #[test]
+    fn extreme_bounds() {
+        let top = range(i64::MAX - 2, i64::MAX, true, 1);
+        assert_eq!(elements(&top), vec![i64::MAX - 2, i64::MAX - 1, i64::MAX]);
+        assert!(top.contains(i64::MAX));
+        assert_eq!(range(i64::MIN, i64::MAX, true, 1).len(), usize::MAX);
+        assert_eq!(elements(&range(i64::MIN + 1, i64::MIN, true, -1)), vec![i64::MIN + 1, i64::MIN]);
+    }
+
+    #[test]
+    fn contains_respects_the_step() {
+        let evens = range(0, 10, true, 2);
+        assert!(evens.contains(0) && evens.contains(10) && evens.contains(4));
+        assert!(!evens.contains(3) && !evens.contains(12) && !evens.contains(-2));
+        let down = range(10, 0, false, -3);
+        assert!(down.contains(10) && down.contains(1));
+        assert!(!down.contains(0) && !down.contains(11));
+    }
+
+    #[test]
+    fn slices() {
+        let evens = range(0, 10, true, 2);
+        assert_eq!(elements(&evens.slice(1..3)), vec![2, 4]);
+        assert_eq!(elements(&evens.slice(3..6)), vec![6, 8, 10]);
+        assert!(evens.slice(2..2).is_empty());
+    }
+
+    #[test]
+    fn equal_when_the_elements_are() {
+        assert_eq!(range(0, 5, false, 1), range(0, 4, true, 1));
+        assert_eq!(range(0, 5, false, 2), range(0, 4, true, 2));
+        assert_eq!(range(3, 3, false, 1), range(7, 0, false, 1));
+        assert_eq!(range(1, 2, false, 1), range(1, 0, false, -5));
+        assert_ne!(range(0, 5, false, 1), range(0, 5, false, 2));
+    }
+
+    #[test]
+    fn display_keeps_the_written_form() {
+        assert_eq!(Obj::Range(range(1, 5, true, 1)).to_string(), "1..=5");
+        assert_eq!(Obj::Range(range(1, 5, false, 2)).to_string(), "1..5 by 2");
+    }
+
+    #[test]
+    fn display_lists_and_maps() {
+        let list = Obj::List(vec![Obj::Int(1).as_ref(), Obj::String("a".into()).as_ref()]);
+        assert_eq!(list.to_string(), "(1, a)");
+        assert_eq!(Obj::List(vec![Obj::Nil.as_ref()]).to_string(), "(nil,)");
+        assert_eq!(Obj::List(vec![]).to_string(), "(,)");
+        let map = Obj::Map([("b".to_string(), Obj::Int(2).as_ref()), ("a".to_string(), list.as_ref())].into_iter().collect());
+        assert_eq!(map.to_string(), "('a': (1, a), 'b': 2)");
+        assert_eq!(Obj::Map(BTreeMap::new()).to_string(), "(:)");
+    }
+
+    #[test]
+    fn display_cycles() {
+        let list = Obj::List(vec![Obj::Int(1).as_ref()]).as_ref();
+        let item = list.clone();
+        if let Obj::List(items) = &mut *list.borrow_mut() {
+            items.push(item);
+        }
+        assert_eq!(list.borrow().to_string(), "(1, (...))");
+        assert_eq!(format!("{:?}", list.borrow()), "List([RefCell { value: Int(1) }, RefCell { value: (...) }])");
     }
 }
\ No newline at end of file
@@ -1,9 +1,59 @@
-use crate::ast::Expr;
-use crate::token::{SpannedToken, Token};
+use logos::Span;
+use crate::ast::{Doc, Expr, FormatSpec, MatchArm, Part, Pattern, SpannedExpr, SpannedPattern};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::error::{Error, Result};
+use crate::profile::{self, DEFAULT_MAX_DEPTH};
+use crate::token::{Spanned, SpannedToken, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Associativity {
+    Left,
+    Right,
+}
+
+/// Built-in binary operators with their precedence, higher binding tighter, and associativity. Levels
+/// follow a 0-9 scale with gaps so new operators can slot in between existing ones; `infix` declarations
+/// may use 1-9, so none bind looser than assignment.
+const INFIX: &[(Token, u8, Associativity)] = &[
+    (Token::Assign, 0, Associativity::Right),
+    (Token::Reassign, 0, Associativity::Right),
+    (Token::Pipeline, 1, Associativity::Left),
+    (Token::Or, 2, Associativity::Left),
+    (Token::And, 3, Associativity::Left),
+    (Token::Equality, 4, Associativity::Left),
+    (Token::NotEqual, 4, Associativity::Left),
+    (Token::LessThan, 4, Associativity::Left),
+    (Token::GreaterThan, 4, Associativity::Left),
+    (Token::LessThanEqual, 4, Associativity::Left),
+    (Token::GreaterThanEqual, 4, Associativity::Left),
+    (Token::DotDot, 5, Associativity::Left),
+    (Token::DotDotEq, 5, Associativity::Left),
+    (Token::Plus, 6, Associativity::Left),
+    (Token::Minus, 6, Associativity::Left),
+    (Token::Star, 7, Associativity::Left),
+    (Token::Slash, 7, Associativity::Left),
+    (Token::Percent, 7, Associativity::Left),
+];
+
+/// Prefix operators with the precedence their operand is parsed at. They bind tighter than every
+/// binary operator, including those declared with `infix` at up to 9, but looser than calls and
+/// property access.
+const PREFIX: &[(Token, u8)] = &[(Token::Minus, 10), (Token::Not, 10)];
 
 pub struct Parser<'a> {
     tokens: &'a [SpannedToken],
     current: usize,
+    errors: Vec<Error>,
+    warnings: Vec<Diagnostic>,
+    docs: Vec<Doc>,
+    /// `INFIX` plus the operators declared with `infix` so far.
+    operators: Vec<(Token, u8, Associativity)>,
+    /// Loops enclosing the current position within the innermost function, for checking `break`.
+    loops: usize,
+    /// Functions enclosing the current position, for checking `return`.
+    functions: usize,
+    /// Expressions enclosing the current position, limited to `DEFAULT_MAX_DEPTH`.
+    depth: usize,
 }
 
 macro_rules! peek_token {
@@ -31,36 +81,221 @@ macro_rules! eat {
         if peek_token!($self, $token) {
             $self.advance();
         } else {
-            panic!("Expected token {:?}", Token::$token);
+            return Err($self.unexpected(&format!("`{}`", Token::$token)));
+        }
+    };
+    ($self:ident, $token:ident, $opened:expr, $label:expr) => {
+        if peek_token!($self, $token) {
+            $self.advance();
+        } else {
+            return Err($self.unexpected(&format!("`{}`", Token::$token)).with_label($opened, $label));
         }
     };
 }
 
 impl<'a> Parser<'a> {
     pub fn new(tokens: &'a [SpannedToken]) -> Self {
-        Self { tokens, current: 0 }
+        Self {
+            tokens,
+            current: 0,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            docs: Vec::new(),
+            operators: INFIX.to_vec(),
+            loops: 0,
+            functions: 0,
+            depth: 0,
+        }
     }
     
-    pub fn parse(&mut self) -> Vec<Expr> {
-        let mut exprs = Vec::new();
+    pub fn parse(&mut self) -> Result<Vec<SpannedExpr>> {
+        let (exprs, mut errors) = self.parse_all();
         
-        while let Some(_) = self.peek() {
-            exprs.push(self.expression());
+        if errors.is_empty() {
+            Ok(exprs)
+        } else {
+            Err(errors.remove(0))
         }
-        
-        exprs
+    }
+
+    /// Parses the whole input, recovering from syntax errors. Erroneous expressions are replaced by
+    /// `Expr::Error` and every error found is returned alongside the partial AST.
+    pub fn parse_all(&mut self) -> (Vec<SpannedExpr>, Vec<Error>) {
+        let exprs = self.statements(None);
+        (exprs, std::mem::take(&mut self.errors))
+    }
+    
+    /// Returns the warnings collected so far, such as non-exhaustive matches.
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+    
+    /// Returns the doc comments attached to definitions so far.
+    pub fn take_docs(&mut self) -> Vec<Doc> {
+        std::mem::take(&mut self.docs)
     }
     
     fn peek(&self) -> Option<&SpannedToken> {
-        self.tokens.get(self.current)
+        self.lookahead(0)
+    }
+
+    /// Returns the `n`th upcoming token, stepping over doc comment trivia.
+    fn lookahead(&self, n: usize) -> Option<&SpannedToken> {
+        self.tokens
+            .get(self.current..)?
+            .iter()
+            .filter(|token| !matches!(token.node, Token::DocComment(_)))
+            .nth(n)
     }
     
     fn advance(&mut self) -> Option<&SpannedToken> {
+        while let Some(SpannedToken { node: Token::DocComment(_), .. }) = self.tokens.get(self.current) {
+            self.current += 1;
+        }
         self.current += 1;
         self.tokens.get(self.current - 1)
     }
+
+    /// Collects the `##` doc comment lines directly ahead of the next token.
+    fn doc_comment(&self) -> Option<String> {
+        let lines: Vec<&str> = self.tokens
+            .get(self.current..)?
+            .iter()
+            .map_while(|token| match &token.node {
+                Token::DocComment(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    fn span(&self) -> Span {
+        match self.peek() {
+            Some(token) => token.span.clone(),
+            None => {
+                let end = self.tokens.last().map_or(0, |token| token.span.end);
+                end..end
+            }
+        }
+    }
+
+    /// Wraps a node in a span running from `start` to the end of the last consumed token.
+    fn finish(&self, start: usize, node: Expr) -> SpannedExpr {
+        Spanned::new(node, start..self.end(start))
+    }
+
+    fn finish_pattern(&self, start: usize, pattern: Pattern) -> SpannedPattern {
+        Spanned::new(pattern, start..self.end(start))
+    }
+
+    fn end(&self, start: usize) -> usize {
+        let end = self.current.checked_sub(1).and_then(|i| self.tokens.get(i)).map_or(start, |token| token.span.end);
+        end.max(start)
+    }
+
+    fn at_separator(&self) -> bool {
+        peek_token!(self, Newline) || peek_token!(self, Semicolon)
+    }
+    
+    fn separators(&mut self) {
+        while match_token!(self, Newline) || match_token!(self, Semicolon) {}
+    }
+
+    fn at(&self, token: Option<&Token>) -> bool {
+        token.is_some_and(|token| self.peek().is_some_and(|next| next.node == *token))
+    }
+
+    /// Parses statements separated by newlines or `;` up to `closer` or the end of input.
+    fn statements(&mut self, closer: Option<&Token>) -> Vec<SpannedExpr> {
+        let mut exprs = Vec::new();
+        
+        loop {
+            self.separators();
+            if self.peek().is_none() || self.at(closer) {
+                return exprs;
+            }
+            
+            exprs.push(self.statement(closer));
+            if !(self.peek().is_none() || self.at(closer) || self.at_separator()) {
+                let error = self.unexpected("a newline or `;`");
+                self.errors.push(error);
+                self.synchronize(closer);
+            }
+        }
+    }
+
+    /// Parses an expression, recording any error and replacing it with `Expr::Error`. `closer` is the
+    /// delimiter the enclosing construct is waiting for, at which recovery stops.
+    fn statement(&mut self, closer: Option<&Token>) -> SpannedExpr {
+        let start = self.current;
+        let offset = self.span().start;
+        let doc = self.doc_comment();
+        match self.expression() {
+            Ok(expr) => {
+                if let (Some(text), Expr::Assign(target, _)) = (doc, &expr.node) {
+                    if let Expr::Identifier(name) = &target.node {
+                        self.docs.push(Doc { name: name.clone(), text, span: expr.span.clone() });
+                    }
+                }
+                expr
+            }
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize(closer);
+                if self.current == start && !self.at(closer) {
+                    self.advance();
+                }
+                self.finish(offset, Expr::Error)
+            }
+        }
+    }
+
+    /// Skips tokens until a statement separator, the enclosing `closer` or the end of input,
+    /// stepping over balanced parentheses, brackets and braces.
+    fn synchronize(&mut self, closer: Option<&Token>) {
+        let mut depth = 0usize;
+        
+        while let Some(token) = self.peek() {
+            if depth == 0 && (self.at_separator() || self.at(closer)) {
+                return;
+            }
+            
+            match token.node {
+                Token::LParen | Token::LBracket | Token::LBrace => depth += 1,
+                Token::RParen | Token::RBracket | Token::RBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> Error {
+        match self.peek() {
+            Some(SpannedToken { node: Token::Error, span }) => Error::parse("Invalid token", span.clone()),
+            Some(SpannedToken { node: Token::Newline, span }) => Error::parse(format!("Expected {}, found end of line", expected), span.clone()),
+            Some(token) => Error::parse(format!("Expected {}, found `{}`", expected, token.node), token.span.clone()),
+            None => Error::parse(format!("Expected {}, found end of input", expected), self.span()),
+        }
+    }
     
-    fn lambda(&mut self) -> Expr {
+    /// Parses `|params| body`. A trailing lambda's body, as in `xs |> filter |x| x > 0 |> map f`, binds
+    /// tighter than `|>` so the pipeline carries on after it.
+    fn lambda(&mut self, trailing: bool) -> Result<SpannedExpr> {
+        let start = self.span().start;
+        let params = self.params()?;
+        
+        // A loop around the lambda can't be left from inside it.
+        let loops = std::mem::take(&mut self.loops);
+        self.functions += 1;
+        let body = if trailing { self.binary(2) } else { self.expression() };
+        self.functions -= 1;
+        self.loops = loops;
+        
+        Ok(self.finish(start, Expr::Lambda(params, Box::new(body?))))
+    }
+    
+    /// Parses the `|a, b|` parameter list of a lambda or `for` loop.
+    fn params(&mut self) -> Result<Vec<String>> {
         eat!(self, Pipe);
         let mut params = Vec::new();
         
@@ -74,139 +309,505 @@ impl<'a> Parser<'a> {
         }
         
         eat!(self, Pipe);
-        let body = self.expression();
-        Expr::Lambda(params, Box::new(body))
+        Ok(params)
     }
     
-    fn block(&mut self) -> Expr {
-        eat!(self, LBrace);
-        let mut exprs = Vec::new();
-        
-        while !peek_token!(self, RBrace) {
-            exprs.push(self.expression());
+    fn loop_body(&mut self, body: fn(&mut Self) -> Result<SpannedExpr>) -> Result<SpannedExpr> {
+        self.loops += 1;
+        let body = body(self);
+        self.loops -= 1;
+        body
+    }
+    
+    /// Parses the optional value of `break` or `return`, which is absent when the expression ends there.
+    fn value(&mut self) -> Result<Option<Box<SpannedExpr>>> {
+        let ended = match self.peek() {
+            None => true,
+            Some(next) => matches!(
+                next.node,
+                Token::Newline | Token::Semicolon | Token::Comma | Token::Colon | Token::Else
+                    | Token::RParen | Token::RBracket | Token::RBrace
+            ),
+        };
+        if ended {
+            Ok(None)
+        } else {
+            self.expression().map(|value| Some(Box::new(value)))
         }
+    }
+    
+    /// Parses `break [value]`, `continue` or `return [value]`, rejecting them outside a loop or function.
+    fn jump(&mut self) -> Result<SpannedExpr> {
+        let start = self.span();
+        let expr = if match_token!(self, Break) {
+            if self.loops == 0 {
+                return Err(Error::parse("`break` outside of a loop", start));
+            }
+            Expr::Break(self.value()?)
+        } else if match_token!(self, Continue) {
+            if self.loops == 0 {
+                return Err(Error::parse("`continue` outside of a loop", start));
+            }
+            Expr::Continue
+        } else {
+            eat!(self, Return);
+            if self.functions == 0 {
+                return Err(Error::parse("`return` outside of a function", start));
+            }
+            Expr::Return(self.value()?)
+        };
         
-        eat!(self, RBrace);
-        Expr::Block(exprs)
+        Ok(self.finish(start.start, expr))
     }
     
-    fn if_expr(&mut self) -> Expr {
+    fn block(&mut self) -> Result<SpannedExpr> {
+        let opened = self.span();
+        eat!(self, LBrace);
+        let exprs = self.statements(Some(&Token::RBrace));
+        eat!(self, RBrace, opened.clone(), "block opened here");
+        Ok(self.finish(opened.start, Expr::Block(exprs)))
+    }
+    
+    fn if_expr(&mut self) -> Result<SpannedExpr> {
+        let start = self.span().start;
         eat!(self, If);
-        let condition = self.expression();
+        let condition = self.expression()?;
         eat!(self, Then);
-        let then_branch = self.expression();
+        let then_branch = self.expression()?;
         let else_branch = if match_token!(self, Else) {
-            Some(Box::new(self.expression()))
+            Some(Box::new(self.expression()?))
         } else {
             None
         };
         
-        Expr::If(Box::new(condition), Box::new(then_branch), else_branch)
+        Ok(self.finish(start, Expr::If(Box::new(condition), Box::new(then_branch), else_branch)))
     }
     
-    fn expression(&mut self) -> Expr {
-        self.logical_and()
-    }
-    
-    fn logical_and(&mut self) -> Expr {
-        let mut expr = self.comparison();
+    fn interpolation(&mut self) -> Result<SpannedExpr> {
+        let opened = self.span();
+        eat!(self, StringStart);
+        let mut parts = Vec::new();
         
-        while match_token!(self, And) {
-            let right = self.comparison();
-            expr = Expr::Binary(Box::new(expr), Token::And, Box::new(right));
+        loop {
+            match self.peek().cloned() {
+                Some(SpannedToken { node: Token::StringLiteral(text), .. }) => {
+                    self.advance();
+                    parts.push(Part::Text(text));
+                }
+                Some(SpannedToken { node: Token::LBrace, span }) => {
+                    self.advance();
+                    let expr = self.expression()?;
+                    let spec = match self.peek().cloned() {
+                        Some(SpannedToken { node: Token::FormatSpec(spec), span }) => {
+                            self.advance();
+                            Some(format_spec(&spec).ok_or_else(|| Error::parse(format!("Invalid format spec `{}`", spec), span))?)
+                        }
+                        _ => None,
+                    };
+                    eat!(self, RBrace, span, "interpolation opened here");
+                    parts.push(Part::Expr(expr, spec));
+                }
+                _ => break,
+            }
         }
         
-        expr
+        eat!(self, StringEnd, opened.clone(), "string opened here");
+        Ok(self.finish(opened.start, Expr::Interpolate(parts)))
     }
-
-    fn comparison(&mut self) -> Expr {
-        let mut expr = self.sum();
+    
+    fn while_expr(&mut self) -> Result<SpannedExpr> {
+        let start = self.span().start;
+        eat!(self, While);
+        let condition = self.expression()?;
+        let body = self.loop_body(Self::block)?;
+        
+        Ok(self.finish(start, Expr::While(Box::new(condition), Box::new(body))))
+    }
+    
+    /// Parses `for (iterable) |item| body`, whose loop variables are written like a trailing lambda's.
+    /// The iterable is a parenthesised atom, so `for (1, 2, 3)` loops over a list literal.
+    fn for_expr(&mut self) -> Result<SpannedExpr> {
+        let start = self.span().start;
+        eat!(self, For);
+        if !peek_token!(self, LParen) {
+            return Err(self.unexpected("`(`"));
+        }
+        let iterable = self.atom()?;
         
-        while match_token!(self, Equality)
-            || match_token!(self, NotEqual)
-            || match_token!(self, LessThan)
-            || match_token!(self, GreaterThan)
-            || match_token!(self, LessThanEqual)
-            || match_token!(self, GreaterThanEqual)
-        {
-            let op = self.tokens[self.current - 1].node.clone();
-            let right = self.sum();
-            expr = Expr::Binary(Box::new(expr), op, Box::new(right));
+        if !peek_token!(self, Pipe) {
+            return Err(self.unexpected("`|` starting the loop variables"));
         }
+        let params = self.params()?;
+        let body = self.loop_body(Self::expression)?;
         
-        expr
+        Ok(self.finish(start, Expr::For(params, Box::new(iterable), Box::new(body))))
     }
     
-    fn sum(&mut self) -> Expr {
-        let mut expr = self.product();
+    fn match_expr(&mut self) -> Result<SpannedExpr> {
+        let start = self.span().start;
+        eat!(self, Match);
+        let subject = self.expression()?;
+        let opened = self.span();
+        eat!(self, LBrace);
+        let mut arms = Vec::new();
+        let mut default = None;
         
-        while match_token!(self, Plus) || match_token!(self, Minus) {
-            let op = self.tokens[self.current - 1].node.clone();
-            let right = self.product();
-            expr = Expr::Binary(Box::new(expr), op, Box::new(right));
+        self.separators();
+        while self.peek().is_some() && !peek_token!(self, RBrace) {
+            if match_token!(self, Else) {
+                eat!(self, Colon);
+                default = Some(Box::new(self.expression()?));
+                match_token!(self, Comma);
+                self.separators();
+                break;
+            }
+            
+            let pattern = self.pattern()?;
+            let guard = if match_token!(self, If) {
+                Some(self.expression()?)
+            } else {
+                None
+            };
+            eat!(self, Colon);
+            let body = self.expression()?;
+            arms.push(MatchArm { pattern, guard, body });
+            match_token!(self, Comma);
+            self.separators();
         }
         
-        expr
+        eat!(self, RBrace, opened, "match opened here");
+        let expr = self.finish(start, Expr::Match(Box::new(subject), arms, default));
+        self.check_exhaustive(&expr);
+        Ok(expr)
     }
-    
-    fn product(&mut self) -> Expr {
-        let mut expr = self.unary();
+
+    /// Warns when a match over booleans or nil leaves some of `true`, `false` and `nil` uncovered. `nil`
+    /// only counts when every pattern is a boolean or nil, since alongside other patterns it usually
+    /// stands for a missing value of another type.
+    fn check_exhaustive(&mut self, expr: &SpannedExpr) {
+        let Expr::Match(_, arms, None) = &expr.node else {
+            return;
+        };
         
-        while match_token!(self, Star) || match_token!(self, Slash) || match_token!(self, Percent) {
-            let op = self.tokens[self.current - 1].node.clone();
-            let right = self.unary();
-            expr = Expr::Binary(Box::new(expr), op, Box::new(right));
+        let mut covered = Vec::new();
+        let mut other = false;
+        for arm in arms {
+            match &arm.pattern.node {
+                Pattern::Wildcard | Pattern::Binding(_) if arm.guard.is_none() => return,
+                Pattern::Wildcard | Pattern::Binding(_) => {}
+                Pattern::Boolean(_) | Pattern::Nil if arm.guard.is_none() => covered.push(&arm.pattern.node),
+                Pattern::Boolean(_) | Pattern::Nil => {}
+                _ => other = true,
+            }
         }
         
-        expr
+        let booleans = covered.iter().any(|pattern| matches!(pattern, Pattern::Boolean(_)));
+        if !booleans && (other || covered.is_empty()) {
+            return;
+        }
+        
+        let mut expected = vec![(Pattern::Boolean(true), "`true`"), (Pattern::Boolean(false), "`false`")];
+        if !other {
+            expected.push((Pattern::Nil, "`nil`"));
+        }
+        let missing: Vec<&str> = expected
+            .iter()
+            .filter(|(pattern, _)| !covered.contains(&pattern))
+            .map(|(_, name)| *name)
+            .collect();
+        
+        let missing = match missing.split_last() {
+            None => return,
+            Some((last, [])) => last.to_string(),
+            Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        };
+        self.warnings.push(Diagnostic::new(
+            Severity::Warning,
+            format!("Non-exhaustive match: {} not covered", missing),
+            Some(expr.span.clone()),
+        ));
     }
     
-    fn unary(&mut self) -> Expr {
-        if match_token!(self, Minus) || match_token!(self, Not) {
-            let op = self.tokens[self.current - 1].node.clone();
-            let right = self.unary();
-            Expr::Unary(op, Box::new(right))
+    fn pattern(&mut self) -> Result<SpannedPattern> {
+        let start = self.span().start;
+        let next = self.peek().cloned();
+        let pattern = if let Some(SpannedToken { node: Token::Number(value), .. }) = next {
+            self.advance();
+            Pattern::Number(value)
+        } else if let Some(SpannedToken { node: Token::Integer(value), .. }) = next {
+            self.advance();
+            Pattern::Integer(value)
+        } else if match_token!(self, Minus) {
+            match self.peek().cloned() {
+                Some(SpannedToken { node: Token::Number(value), .. }) => {
+                    self.advance();
+                    Pattern::Number(-value)
+                }
+                Some(SpannedToken { node: Token::Integer(value), .. }) => {
+                    self.advance();
+                    Pattern::Integer(-value)
+                }
+                _ => return Err(self.unexpected("a number")),
+            }
+        } else if let Some(SpannedToken { node: Token::StringLiteral(value), .. }) = next {
+            self.advance();
+            Pattern::StringLiteral(value)
+        } else if match_token!(self, True) {
+            Pattern::Boolean(true)
+        } else if match_token!(self, False) {
+            Pattern::Boolean(false)
+        } else if match_token!(self, Nil) {
+            Pattern::Nil
+        } else if let Some(SpannedToken { node: Token::Identifier(name), .. }) = next {
+            self.advance();
+            if name == "_" {
+                Pattern::Wildcard
+            } else {
+                Pattern::Binding(name)
+            }
+        } else if peek_token!(self, LParen) {
+            return self.compound_pattern();
         } else {
-            self.assign()
-        }
+            return Err(self.unexpected("a pattern"));
+        };
+        
+        Ok(self.finish_pattern(start, pattern))
     }
     
-    fn assign(&mut self) -> Expr {
-        let expr = self.call();
+    /// Parses a parenthesised list pattern `(a, b, ..rest)`, map pattern `('key': p)` or grouped pattern.
+    fn compound_pattern(&mut self) -> Result<SpannedPattern> {
+        let opened = self.span();
+        eat!(self, LParen);
         
-        if match_token!(self, Assign) {
-            let value = self.expression();
-            Expr::Assign(Box::new(expr), Box::new(value))
+        let pattern = if match_token!(self, Comma) {
+            eat!(self, RParen, opened.clone(), "list pattern opened here");
+            Pattern::List(Vec::new(), None)
+        } else if match_token!(self, Colon) {
+            eat!(self, RParen, opened.clone(), "map pattern opened here");
+            Pattern::Map(Vec::new())
+        } else if matches!(
+            (self.peek(), self.lookahead(1)),
+            (Some(SpannedToken { node: Token::StringLiteral(_), .. }), Some(SpannedToken { node: Token::Colon, .. }))
+        ) {
+            let mut entries = Vec::new();
+            
+            while let Some(SpannedToken { node: Token::StringLiteral(key), .. }) = self.peek().cloned() {
+                self.advance();
+                eat!(self, Colon);
+                entries.push((key, self.pattern()?));
+                
+                if !match_token!(self, Comma) {
+                    break;
+                }
+            }
+            
+            eat!(self, RParen, opened.clone(), "map pattern opened here");
+            Pattern::Map(entries)
         } else {
-            expr
+            let mut items = Vec::new();
+            let mut rest = None;
+            let mut list = false;
+            
+            while !peek_token!(self, RParen) {
+                if peek_token!(self, DotDot) {
+                    let start = self.span().start;
+                    self.advance();
+                    rest = Some(Box::new(match self.peek().cloned() {
+                        Some(SpannedToken { node: Token::Identifier(name), .. }) => {
+                            self.advance();
+                            let pattern = if name == "_" { Pattern::Wildcard } else { Pattern::Binding(name) };
+                            self.finish_pattern(start, pattern)
+                        }
+                        _ => self.finish_pattern(start, Pattern::Wildcard),
+                    }));
+                    list = true;
+                    match_token!(self, Comma);
+                    break;
+                }
+                
+                items.push(self.pattern()?);
+                
+                if !match_token!(self, Comma) {
+                    break;
+                }
+                list = true;
+            }
+            
+            eat!(self, RParen, opened.clone(), "list pattern opened here");
+            if !list && items.len() == 1 {
+                items.remove(0).node
+            } else {
+                Pattern::List(items, rest)
+            }
+        };
+        
+        Ok(self.finish_pattern(opened.start, pattern))
+    }
+    
+    fn expression(&mut self) -> Result<SpannedExpr> {
+        self.binary(0)
+    }
+    
+    /// Parses operators binding at least as tightly as `min`. Every nested expression passes through
+    /// here, which makes it the place to bound nesting.
+    fn binary(&mut self, min: u8) -> Result<SpannedExpr> {
+        if self.depth >= DEFAULT_MAX_DEPTH {
+            return Err(Error::parse("Expression is nested too deeply", self.span()));
         }
+        self.depth += 1;
+        let result = profile::grow(|| self.climb(min));
+        self.depth -= 1;
+        result
+    }
+
+    /// Precedence climbing over `INFIX` and the declared operators.
+    fn climb(&mut self, min: u8) -> Result<SpannedExpr> {
+        let start = self.span().start;
+        let expr = self.prefix()?;
+        self.climb_from(start, expr, min)
     }
     
-    fn call(&mut self) -> Expr {
-        let mut expr = self.atom();
-        if peek_token!(self, LParen) || peek_token!(self, Pipe) || peek_token!(self, Dot) {
+    /// Continues precedence climbing with `expr`, which began at `start`, as the left operand.
+    fn climb_from(&mut self, start: usize, mut expr: SpannedExpr, min: u8) -> Result<SpannedExpr> {
+        while let Some(next) = self.peek() {
+            let Some((op, precedence, associativity)) = self.operators.iter().find(|(op, _, _)| *op == next.node).cloned() else {
+                if let Token::Operator(op) = &next.node {
+                    return Err(Error::parse(format!("Unknown operator `{}`", op), next.span.clone()));
+                }
+                break;
+            };
+            if precedence < min {
+                break;
+            }
+            self.advance();
+            
+            let next = match associativity {
+                Associativity::Left => precedence + 1,
+                Associativity::Right => precedence,
+            };
+            if op == Token::Assign {
+                check_target(&expr, false)?;
+                let value = self.binary(next)?;
+                expr = self.finish(start, Expr::Assign(Box::new(expr), Box::new(value)));
+            } else if op == Token::Reassign {
+                let Expr::Identifier(name) = &expr.node else {
+                    return Err(Error::parse("Only variables can be reassigned with `=`", expr.span.clone()));
+                };
+                let name = name.clone();
+                let value = self.binary(next)?;
+                expr = self.finish(start, Expr::Reassign(name, Box::new(value)));
+            } else if op == Token::Pipeline {
+                // `x |> f(a)` is `f(x, a)` and `x |> f` is `f(x)`, so only a callee is parsed on the right.
+                let callee = self.call()?;
+                let call = match callee.node {
+                    Expr::Call(function, args) if matches!(function.node, Expr::Identifier(_)) => {
+                        Expr::Pipeline(function, std::iter::once(expr).chain(args).collect())
+                    }
+                    Expr::Call(function, args) => Expr::Call(function, std::iter::once(expr).chain(args).collect()),
+                    Expr::Identifier(_) => Expr::Pipeline(Box::new(callee), vec![expr]),
+                    _ => Expr::Call(Box::new(callee), vec![expr]),
+                };
+                expr = self.finish(start, call);
+            } else if op == Token::DotDot || op == Token::DotDotEq {
+                let end = self.binary(next)?;
+                let step = if match_token!(self, By) { Some(Box::new(self.binary(next)?)) } else { None };
+                expr = self.finish(start, Expr::Range(Box::new(expr), Box::new(end), op == Token::DotDotEq, step));
+            } else {
+                let right = self.binary(next)?;
+                expr = self.finish(start, Expr::Binary(Box::new(expr), op, Box::new(right)));
+            }
+        }
+        
+        Ok(expr)
+    }
+    
+    /// Parses `infix 6 <+> := value`, which binds `value` to the variable `<+>` and makes `<+>` a
+    /// left-associative operator at that precedence for the rest of the module.
+    fn infix(&mut self) -> Result<SpannedExpr> {
+        let start = self.span().start;
+        eat!(self, Infix);
+        let precedence = match self.peek() {
+            Some(SpannedToken { node: Token::Integer(n @ 1..=9), .. }) => *n as u8,
+            _ => return Err(self.unexpected("a precedence from 1 to 9")),
+        };
+        self.advance();
+        let (name, span) = match self.peek() {
+            Some(SpannedToken { node: Token::Operator(name), span }) => (name.clone(), span.clone()),
+            _ => return Err(self.unexpected("an operator")),
+        };
+        self.advance();
+        eat!(self, Assign);
+        let value = self.expression()?;
+        
+        let op = Token::Operator(name.clone());
+        self.operators.retain(|(existing, _, _)| *existing != op);
+        self.operators.push((op, precedence, Associativity::Left));
+        let target = Spanned::new(Expr::Identifier(name), span);
+        Ok(self.finish(start, Expr::Assign(Box::new(target), Box::new(value))))
+    }
+    
+    fn prefix(&mut self) -> Result<SpannedExpr> {
+        let start = self.span().start;
+        match self.peek().and_then(|next| PREFIX.iter().find(|(op, _)| *op == next.node)) {
+            Some((op, precedence)) => {
+                self.advance();
+                let right = self.binary(*precedence)?;
+                Ok(self.finish(start, Expr::Unary(op.clone(), Box::new(right))))
+            }
+            None => self.call(),
+        }
+    }
+    
+    fn call(&mut self) -> Result<SpannedExpr> {
+        let start = self.span().start;
+        let mut expr = self.atom()?;
+        if peek_token!(self, LParen) || peek_token!(self, LBracket) || peek_token!(self, Pipe) || peek_token!(self, Dot) {
             loop {
                 if peek_token!(self, LParen) {
                     let mut args = Vec::new();
+                    let opened = self.span();
                     eat!(self, LParen);
 
                     while !peek_token!(self, RParen) {
-                        args.push(self.expression());
+                        args.push(self.statement(Some(&Token::RParen)));
 
                         if !match_token!(self, Comma) {
                             break;
                         }
                     }
 
-                    eat!(self, RParen);
+                    eat!(self, RParen, opened, "argument list opened here");
                     
                     if peek_token!(self, Pipe) {
-                        args.push(self.lambda());
+                        args.push(self.lambda(true)?);
                     }
                     
-                    expr = Expr::Call(Box::new(expr), args);
+                    expr = self.finish(start, Expr::Call(Box::new(expr), args));
+                } else if peek_token!(self, LBracket) {
+                    let opened = self.span();
+                    eat!(self, LBracket);
+                    // A slice's bounds bind tighter than ranges, which leaves `..` to separate them. Without
+                    // a `..` after it, the operand is just the start of an index expression.
+                    let index_start = self.span().start;
+                    let from = if peek_token!(self, DotDot) { None } else { Some(self.binary(6)?) };
+                    
+                    let node = match from {
+                        Some(index) if !peek_token!(self, DotDot) => {
+                            let index = self.climb_from(index_start, index, 0)?;
+                            eat!(self, RBracket, opened, "index opened here");
+                            Expr::Index(Box::new(expr), Box::new(index))
+                        }
+                        from => {
+                            eat!(self, DotDot);
+                            let to = if peek_token!(self, RBracket) { None } else { Some(Box::new(self.binary(6)?)) };
+                            eat!(self, RBracket, opened, "slice opened here");
+                            Expr::Slice(Box::new(expr), from.map(Box::new), to)
+                        }
+                    };
+                    expr = self.finish(start, node);
                 } else if peek_token!(self, Pipe) {
-                    expr = Expr::Call(Box::new(expr), vec![self.lambda()]);
+                    let lambda = self.lambda(true)?;
+                    expr = self.finish(start, Expr::Call(Box::new(expr), vec![lambda]));
                 } else if peek_token!(self, Dot) {
                     eat!(self, Dot);
                     let next = self.peek().cloned();
@@ -214,23 +815,57 @@ impl<'a> Parser<'a> {
                         self.advance();
                         name
                     } else {
-                        panic!("Expected identifier");
+                        return Err(self.unexpected("a property name"));
                     };
-                    expr = Expr::Property(Box::new(expr), property.clone());
+                    expr = self.finish(start, Expr::Property(Box::new(expr), property.clone()));
                 } else {
                     break;
                 }
             }
         }
         
-        expr
+        Ok(expr)
+    }
+    
+    /// Consumes the operator opening a section like `(+ 1)`. `-` is left out, since `(- 1)` negates, as
+    /// are `:=`, `=`, `|>` and ranges, which aren't binary operations.
+    fn section(&mut self) -> Option<(Token, Span)> {
+        let next = self.peek()?;
+        let sectionable = !matches!(next.node, Token::Minus | Token::Assign | Token::Reassign | Token::Pipeline | Token::DotDot | Token::DotDotEq)
+            && self.operators.iter().any(|(op, _, _)| *op == next.node);
+        let section = sectionable.then(|| (next.node.clone(), next.span.clone()));
+        if section.is_some() {
+            self.advance();
+        }
+        section
+    }
+    
+    /// Arbitrary-precision literals, which only lex when their cargo feature is enabled.
+    fn big_literal(&mut self) -> Option<Expr> {
+        #[cfg(feature = "bigint")]
+        if let Some(SpannedToken { node: Token::BigInt(value), .. }) = self.peek().cloned() {
+            self.advance();
+            return Some(Expr::BigInt(value));
+        }
+        #[cfg(feature = "decimal")]
+        if let Some(SpannedToken { node: Token::Decimal(value), .. }) = self.peek().cloned() {
+            self.advance();
+            return Some(Expr::Decimal(value));
+        }
+        None
     }
     
-    fn atom(&mut self) -> Expr {
+    fn atom(&mut self) -> Result<SpannedExpr> {
+        let start = self.span().start;
         let next = self.peek().cloned();
-        if let Some(SpannedToken { node: Token::Number(value), .. }) = next {
+        let expr = if let Some(SpannedToken { node: Token::Number(value), .. }) = next {
             self.advance();
             Expr::Number(value)
+        } else if let Some(SpannedToken { node: Token::Integer(value), .. }) = next {
+            self.advance();
+            Expr::Integer(value)
+        } else if let Some(expr) = self.big_literal() {
+            expr
         } else if let Some(SpannedToken { node: Token::StringLiteral(value), .. }) = next {
             self.advance();
             Expr::StringLiteral(value)
@@ -244,57 +879,161 @@ impl<'a> Parser<'a> {
             self.advance();
             Expr::Identifier(name)
         } else if peek_token!(self, LBrace) {
-            self.block()
+            return self.block();
         } else if peek_token!(self, Pipe) {
-            self.lambda()
+            return self.lambda(false);
         } else if peek_token!(self, If) {
-            self.if_expr()
-        } else if match_token!(self, LParen) {
+            return self.if_expr();
+        } else if peek_token!(self, StringStart) {
+            return self.interpolation();
+        } else if peek_token!(self, While) {
+            return self.while_expr();
+        } else if peek_token!(self, Match) {
+            return self.match_expr();
+        } else if peek_token!(self, Infix) {
+            return self.infix();
+        } else if peek_token!(self, For) {
+            return self.for_expr();
+        } else if peek_token!(self, Break) || peek_token!(self, Continue) || peek_token!(self, Return) {
+            return self.jump();
+        } else if peek_token!(self, LParen) {
+            let opened = self.span();
+            self.advance();
             if match_token!(self, Comma) {
-                eat!(self, RParen);
+                eat!(self, RParen, opened, "list opened here");
                 Expr::List(Vec::new())
             } else if match_token!(self, Colon) {
-                eat!(self, RParen);
+                eat!(self, RParen, opened, "map opened here");
                 Expr::Map(Vec::new())
+            } else if let Some((op, span)) = self.section() {
+                let right = self.expression()?;
+                eat!(self, RParen, opened, "section opened here");
+                let operand = Spanned::new(Expr::Identifier("$1".to_string()), span);
+                let body = self.finish(opened.start, Expr::Binary(Box::new(operand), op, Box::new(right)));
+                Expr::Lambda(vec!["$1".to_string()], Box::new(body))
             } else {
-                let expr = self.expression();
+                let expr = self.statement(Some(&Token::RParen));
                 
                 if match_token!(self, Comma) {
                     let mut list = vec![expr];
                     
                     while !peek_token!(self, RParen) {
-                        list.push(self.expression());
+                        list.push(self.statement(Some(&Token::RParen)));
                         
                         if !match_token!(self, Comma) {
                             break;
                         }
                     }
                     
-                    eat!(self, RParen);
+                    eat!(self, RParen, opened, "list opened here");
                     Expr::List(list)
                 } else if match_token!(self, Colon) {
-                    let mut map = vec![(expr, self.expression())];
+                    let mut map = vec![(expr, self.statement(Some(&Token::RParen)))];
                     
                     while !peek_token!(self, RParen) {
                         if !match_token!(self, Comma) {
                             break;
                         }
                         
-                        let key = self.expression();
+                        let key = self.statement(Some(&Token::RParen));
                         eat!(self, Colon);
-                        let value = self.expression();
+                        let value = self.statement(Some(&Token::RParen));
                         map.push((key, value));
                     }
                     
-                    eat!(self, RParen);
+                    eat!(self, RParen, opened, "map opened here");
                     Expr::Map(map)
                 } else {
-                    eat!(self, RParen);
-                    expr
+                    eat!(self, RParen, opened, "parenthesis opened here");
+                    placeholders(expr)
                 }
             }
         } else {
-            panic!("Unexpected token {:?}", self.peek().unwrap().node);
+            return Err(self.unexpected("an expression"));
+        };
+
+        Ok(self.finish(start, expr))
+    }
+}
+
+/// Turns a parenthesised operation with `_` operands, like `(_ * 2)` or `(_ + _ * 2)`, into a lambda
+/// taking one parameter per `_`, numbered left to right through the whole operator tree. The
+/// parameters are named `$1`, `$2`, ..., which no identifier can shadow.
+fn placeholders(expr: SpannedExpr) -> Expr {
+    if !matches!(expr.node, Expr::Binary(..)) {
+        return expr.node;
+    }
+    
+    let mut params = Vec::new();
+    let body = replace_placeholders(expr, &mut params);
+    if params.is_empty() {
+        body.node
+    } else {
+        Expr::Lambda(params, Box::new(body))
+    }
+}
+
+fn replace_placeholders(expr: SpannedExpr, params: &mut Vec<String>) -> SpannedExpr {
+    let node = match expr.node {
+        Expr::Identifier(name) if name == "_" => {
+            params.push(format!("${}", params.len() + 1));
+            Expr::Identifier(params[params.len() - 1].clone())
+        }
+        Expr::Binary(left, op, right) => {
+            let left = replace_placeholders(*left, params);
+            let right = replace_placeholders(*right, params);
+            Expr::Binary(Box::new(left), op, Box::new(right))
+        }
+        Expr::Unary(op, right) => Expr::Unary(op, Box::new(replace_placeholders(*right, params))),
+        node => node,
+    };
+    Spanned::new(node, expr.span)
+}
+
+/// Checks that the left-hand side of `:=` is an identifier, an index or a (possibly nested) list or map
+/// of targets.
+/// Nested targets may carry a default, written `target := default`.
+fn check_target(expr: &SpannedExpr, nested: bool) -> Result<()> {
+    match &expr.node {
+        Expr::Identifier(_) | Expr::Index(..) | Expr::Error => Ok(()),
+        Expr::List(items) => items.iter().try_for_each(|item| check_target(item, true)),
+        Expr::Map(entries) => entries.iter().try_for_each(|(_, value)| check_target(value, true)),
+        Expr::Assign(target, _) if nested => check_target(target, true),
+        _ => Err(Error::parse("Invalid assignment target", expr.span.clone())),
+    }
+}
+
+/// Parses the text after the `:` in an interpolation, e.g. `.2`, `>8` or `*^10`.
+fn format_spec(spec: &str) -> Option<FormatSpec> {
+    let mut chars = spec.chars().peekable();
+    let mut result = FormatSpec { fill: ' ', align: None, zero: false, width: None, precision: None };
+    
+    let mut lookahead = spec.chars();
+    match (lookahead.next(), lookahead.next()) {
+        (Some(fill), Some(align @ ('<' | '^' | '>'))) => {
+            result.fill = fill;
+            result.align = Some(align);
+            chars.nth(1);
         }
+        (Some(align @ ('<' | '^' | '>')), _) => {
+            result.align = Some(align);
+            chars.next();
+        }
+        _ => {}
     }
-}
\ No newline at end of file
+    
+    result.zero = chars.next_if_eq(&'0').is_some();
+    let digits = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        let mut digits = String::new();
+        while let Some(c) = chars.next_if(char::is_ascii_digit) {
+            digits.push(c);
+        }
+        digits.parse().ok()
+    };
+    result.width = digits(&mut chars);
+    if chars.next_if_eq(&'.').is_some() {
+        result.precision = Some(digits(&mut chars)?);
+    }
+    
+    chars.next().is_none().then_some(result)
+}
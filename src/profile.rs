@@ -1,21 +1,32 @@
+/// How deeply calls may nest unless a profile says otherwise. The parser allows expressions to nest as
+/// deeply.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+/// Runs `f`, first moving to a fresh stack segment if little native stack is left. The interpreter
+/// and parser recurse through it so that only the depth limits, not the host's stack size, decide how
+/// deep scripts can go.
+pub(crate) fn grow<T>(f: impl FnOnce() -> T) -> T {
+    stacker::maybe_grow(128 * 1024, 2 * 1024 * 1024, f)
+}
+
 pub struct Profile {
-    max_stack_depth: Option<usize>,
-    max_heap_size: Option<usize>,
-    max_time_ms: Option<usize>,
-    capabilities: Capabilities
+    pub max_stack_depth: Option<usize>,
+    pub max_heap_size: Option<usize>,
+    pub max_time_ms: Option<usize>,
+    pub capabilities: Capabilities
 }
 
 pub struct Capabilities {
-    io: bool,
-    network: bool,
-    filesystem: bool,
-    async_await: bool,
+    pub io: bool,
+    pub network: bool,
+    pub filesystem: bool,
+    pub async_await: bool,
 }
 
-impl Profile {
-    pub fn default() -> Self {
+impl Default for Profile {
+    fn default() -> Self {
         Self {
-            max_stack_depth: None,
+            max_stack_depth: Some(DEFAULT_MAX_DEPTH),
             max_heap_size: None,
             max_time_ms: None,
             capabilities: Capabilities {
@@ -26,4 +37,4 @@ impl Profile {
             }
         }
     }
-}
\ No newline at end of file
+}
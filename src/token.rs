@@ -1,6 +1,10 @@
 use std::hash::Hash;
-use logos::{Logos, Span};
+use logos::{FilterResult, Logos, Span};
 use std::fmt::Display;
+use std::iter::Peekable;
+use std::str::CharIndices;
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+use crate::error::Error;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Spanned<T> {
@@ -8,8 +12,29 @@ pub struct Spanned<T> {
     pub span: Span,
 }
 
+#[derive(Debug, PartialEq, Clone, Default)]
+pub enum LexError {
+    #[default]
+    UnexpectedCharacter,
+    UnterminatedComment,
+    UnterminatedString,
+    InvalidEscape(Span),
+    IntegerOverflow,
+    #[cfg(feature = "decimal")]
+    DecimalOverflow,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TemplatePart {
+    Text(String, Span),
+    /// An interpolated expression's source range, its optional `:spec` and the offset of the closing `}`.
+    Interpolation { expr: Span, spec: Option<(String, Span)>, close: usize },
+}
+
 #[derive(Logos, Debug, PartialEq, Clone)]
-#[logos(skip r"[ \t\n\f]+")]
+#[logos(error = LexError)]
+#[logos(skip r"[ \t\r\f]+")]
+#[logos(skip r"#([^#\[\n][^\n]*)?")]
 pub enum Token {
     #[token("if")]
     If,
@@ -25,27 +50,57 @@ pub enum Token {
     Not,
     #[token("while")]
     While,
+    #[token("for")]
+    For,
+    #[token("break")]
+    Break,
+    #[token("continue")]
+    Continue,
+    #[token("return")]
+    Return,
+    #[token("by")]
+    By,
     #[token("match")]
     Match,
+    #[token("infix")]
+    Infix,
 
     #[token(":=")]
     Assign,
+    /// `=`, which updates an existing variable, including one captured from an enclosing function.
+    #[token("=")]
+    Reassign,
     #[token(":")]
     Colon,
     #[token(",")]
     Comma,
+    #[token(";")]
+    Semicolon,
+    /// A line break that ends a statement. `lex` drops the ones that cannot, see `newlines`.
+    #[token("\n")]
+    Newline,
     #[token("|")]
     Pipe,
+    #[token("|>")]
+    Pipeline,
     #[token("(")]
     LParen,
     #[token(")")]
     RParen,
+    #[token("[")]
+    LBracket,
+    #[token("]")]
+    RBracket,
     #[token("{")]
     LBrace,
     #[token("}")]
     RBrace,
     #[token(".")]
     Dot,
+    #[token("..")]
+    DotDot,
+    #[token("..=")]
+    DotDotEq,
     #[token("+")]
     Plus,
     #[token("-")]
@@ -68,19 +123,54 @@ pub enum Token {
     LessThanEqual,
     #[token(">=")]
     GreaterThanEqual,
+    /// A run of operator symbols with no built-in meaning, such as `<+>`, declared with `infix`. It can't
+    /// contain `-`, so `x==-1` still lexes as `==` followed by a negation.
+    #[regex(r"[+*/%<>=!&^~?@$]+", |lex| lex.slice().to_string(), priority = 1)]
+    Operator(String),
 
-    #[regex("[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice().to_string())]
+    /// A UAX #31 identifier, normalised to NFC so differently composed spellings name the same variable.
+    #[regex(r"[\p{XID_Start}_][\p{XID_Continue}]*", |lex| identifier(lex.slice()))]
     Identifier(String),
-    #[regex(r#""[^"]*"|'[^']*'"#, |lex| lex.slice().to_string())]
+    /// Produced by the lexer for every string literal and expanded by `lex` into either a plain
+    /// `StringLiteral` or an interpolation sequence, so the parser never sees it.
+    #[token("\"", |lex| string(lex, "\"", false))]
+    #[token("'", |lex| string(lex, "'", false))]
+    #[token("\"\"\"", |lex| string(lex, "\"\"\"", false))]
+    #[token("'''", |lex| string(lex, "'''", false))]
+    #[token("r\"", |lex| string(lex, "\"", true))]
+    #[token("r'", |lex| string(lex, "'", true))]
+    #[token("r\"\"\"", |lex| string(lex, "\"\"\"", true))]
+    #[token("r'''", |lex| string(lex, "'''", true))]
+    Template(Vec<TemplatePart>),
     StringLiteral(String),
-    #[regex(r#"(?:\d[\d_]*)(?:\.?(\d|_)*)"#, |lex| lex.slice().replace("_", "").parse::<f64>().ok())]
+    StringStart,
+    StringEnd,
+    FormatSpec(String),
+    #[regex(r"[0-9][0-9_]*\.[0-9][0-9_]*([eE][+-]?[0-9][0-9_]*)?", |lex| lex.slice().replace('_', "").parse::<f64>().ok())]
+    #[regex(r"[0-9][0-9_]*[eE][+-]?[0-9][0-9_]*", |lex| lex.slice().replace('_', "").parse::<f64>().ok())]
     Number(f64),
+    #[regex(r"[0-9][0-9_]*", |lex| integer(lex.slice(), 10))]
+    #[regex(r"0x[0-9a-fA-F_]+", |lex| integer(&lex.slice()[2..], 16))]
+    #[regex(r"0o[0-7_]+", |lex| integer(&lex.slice()[2..], 8))]
+    #[regex(r"0b[01_]+", |lex| integer(&lex.slice()[2..], 2))]
+    Integer(i64),
+    #[cfg(feature = "bigint")]
+    #[regex(r"[0-9][0-9_]*n", |lex| lex.slice()[..lex.slice().len() - 1].replace('_', "").parse::<num_bigint::BigInt>().ok())]
+    BigInt(num_bigint::BigInt),
+    #[cfg(feature = "decimal")]
+    #[regex(r"[0-9][0-9_]*(\.[0-9][0-9_]*)?d", decimal)]
+    Decimal(rust_decimal::Decimal),
     #[token("true")]
     True,
     #[token("false")]
     False,
     #[token("nil")]
     Nil,
+    #[regex(r"##[^\n]*", |lex| lex.slice()[2..].strip_prefix(' ').unwrap_or(&lex.slice()[2..]).to_string())]
+    DocComment(String),
+    /// Never produced: the callback skips nestable `#[ ... ]#` block comments.
+    #[token("#[", block_comment)]
+    BlockComment,
     Error,
 }
 
@@ -94,16 +184,30 @@ impl Display for Token {
             Token::Or => write!(f, "or"),
             Token::Not => write!(f, "not"),
             Token::While => write!(f, "while"),
+            Token::For => write!(f, "for"),
+            Token::Break => write!(f, "break"),
+            Token::Continue => write!(f, "continue"),
+            Token::Return => write!(f, "return"),
+            Token::By => write!(f, "by"),
             Token::Match => write!(f, "match"),
+            Token::Infix => write!(f, "infix"),
             Token::Assign => write!(f, ":="),
+            Token::Reassign => write!(f, "="),
             Token::Colon => write!(f, ":"),
             Token::Comma => write!(f, ","),
+            Token::Semicolon => write!(f, ";"),
+            Token::Newline => write!(f, "newline"),
             Token::Pipe => write!(f, "|"),
+            Token::Pipeline => write!(f, "|>"),
             Token::LParen => write!(f, "("),
             Token::RParen => write!(f, ")"),
+            Token::LBracket => write!(f, "["),
+            Token::RBracket => write!(f, "]"),
             Token::LBrace => write!(f, "{{"),
             Token::RBrace => write!(f, "}}"),
             Token::Dot => write!(f, "."),
+            Token::DotDot => write!(f, ".."),
+            Token::DotDotEq => write!(f, "..="),
             Token::Plus => write!(f, "+"),
             Token::Minus => write!(f, "-"),
             Token::Star => write!(f, "*"),
@@ -115,12 +219,24 @@ impl Display for Token {
             Token::GreaterThan => write!(f, ">"),
             Token::LessThanEqual => write!(f, "<="),
             Token::GreaterThanEqual => write!(f, ">="),
+            Token::Operator(op) => write!(f, "{}", op),
             Token::Identifier(name) => write!(f, "{}", name),
-            Token::StringLiteral(s) => write!(f, "{}", s),
+            Token::Template(_) => write!(f, "string"),
+            Token::StringLiteral(s) => write!(f, "{:?}", s),
+            Token::StringStart => write!(f, "start of string"),
+            Token::StringEnd => write!(f, "end of string"),
+            Token::FormatSpec(spec) => write!(f, ":{}", spec),
             Token::Number(n) => write!(f, "{}", n),
+            Token::Integer(n) => write!(f, "{}", n),
+            #[cfg(feature = "bigint")]
+            Token::BigInt(n) => write!(f, "{}n", n),
+            #[cfg(feature = "decimal")]
+            Token::Decimal(n) => write!(f, "{}d", n),
             Token::True => write!(f, "true"),
             Token::False => write!(f, "false"),
             Token::Nil => write!(f, "nil"),
+            Token::DocComment(text) => write!(f, "## {}", text),
+            Token::BlockComment => write!(f, "#["),
             Token::Error => write!(f, "error"),
         }
     }
@@ -136,8 +252,424 @@ impl Hash for Token {
 
 pub type SpannedToken = Spanned<Token>;
 
-impl SpannedToken {
-    pub fn new(token: Token, span: Span) -> Self {
-        SpannedToken { node: token, span }
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Spanned { node, span }
+    }
+}
+
+fn identifier(name: &str) -> String {
+    match is_nfc_quick(name.chars()) {
+        IsNormalized::Yes => name.to_string(),
+        _ => name.nfc().collect(),
+    }
+}
+
+fn integer(digits: &str, radix: u32) -> Result<i64, LexError> {
+    let digits = digits.replace('_', "");
+    if digits.is_empty() {
+        return Err(LexError::UnexpectedCharacter);
+    }
+    i64::from_str_radix(&digits, radix).map_err(|_| LexError::IntegerOverflow)
+}
+
+#[cfg(feature = "decimal")]
+fn decimal(lex: &mut logos::Lexer<Token>) -> Result<rust_decimal::Decimal, LexError> {
+    let digits = lex.slice()[..lex.slice().len() - 1].replace('_', "");
+    digits.parse().map_err(|_| LexError::DecimalOverflow)
+}
+
+/// Scans a string literal up to its closing `quote`, decoding escapes unless `raw`. Only triple-quoted
+/// strings may span several lines. `{...}` interpolations are recorded as source ranges to lex later.
+fn string(lex: &mut logos::Lexer<Token>, quote: &str, raw: bool) -> Result<Vec<TemplatePart>, LexError> {
+    let rest = lex.remainder();
+    let start = lex.span().end;
+    let multiline = quote.len() == 3;
+    let mut parts = Vec::new();
+    let mut value = String::new();
+    let mut text_start = 0;
+    let mut chars = rest.char_indices().peekable();
+    
+    while let Some((i, c)) = chars.next() {
+        if rest[i..].starts_with(quote) {
+            parts.push(TemplatePart::Text(value, start + text_start..start + i));
+            lex.bump(i + quote.len());
+            return Ok(parts);
+        }
+        
+        match c {
+            '\n' if !multiline => break,
+            '\\' if !raw => {
+                let escaped = match chars.next() {
+                    Some((_, 'n')) => '\n',
+                    Some((_, 't')) => '\t',
+                    Some((_, 'r')) => '\r',
+                    Some((_, '0')) => '\0',
+                    Some((_, '\\')) => '\\',
+                    Some((_, '"')) => '"',
+                    Some((_, '\'')) => '\'',
+                    Some((_, '{')) => '{',
+                    Some((_, '}')) => '}',
+                    Some((_, 'u')) => unicode_escape(rest, i, &mut chars)
+                        .map_err(|end| LexError::InvalidEscape(start + i..start + end))?,
+                    Some((j, c)) => return Err(LexError::InvalidEscape(start + i..start + j + c.len_utf8())),
+                    None => break,
+                };
+                value.push(escaped);
+            }
+            '{' if !raw => {
+                parts.push(TemplatePart::Text(std::mem::take(&mut value), start + text_start..start + i));
+                let (expr_end, close) = interpolation(rest, i + 1, multiline)?;
+                let spec = (expr_end < close).then(|| (rest[expr_end + 1..close].to_string(), start + expr_end..start + close));
+                parts.push(TemplatePart::Interpolation {
+                    expr: start + i + 1..start + expr_end,
+                    spec,
+                    close: start + close,
+                });
+                while chars.next_if(|(j, _)| *j <= close).is_some() {}
+                text_start = close + 1;
+            }
+            c => value.push(c),
+        }
+    }
+    
+    Err(LexError::UnterminatedString)
+}
+
+/// Finds the end of an interpolated expression starting at byte `from` of `rest`, skipping nested
+/// brackets and strings. Returns where the expression ends (at a `:` format spec or the closing `}`)
+/// and where the closing `}` is.
+fn interpolation(rest: &str, from: usize, multiline: bool) -> Result<(usize, usize), LexError> {
+    let mut depth = 0usize;
+    let mut expr_end = None;
+    let mut chars = rest[from..].char_indices().map(|(i, c)| (from + i, c)).peekable();
+    
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\n' if !multiline => break,
+            '}' if depth == 0 => return Ok((expr_end.unwrap_or(i), i)),
+            _ if expr_end.is_some() => {}
+            '{' | '(' => depth += 1,
+            '}' | ')' => depth = depth.saturating_sub(1),
+            ':' if depth == 0 && chars.peek().is_none_or(|(_, next)| *next != '=') => expr_end = Some(i),
+            '"' | '\'' => {
+                while let Some((_, inner)) = chars.next() {
+                    match inner {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '\n' if !multiline => return Err(LexError::UnterminatedString),
+                        inner if inner == c => break,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    Err(LexError::UnterminatedString)
+}
+
+/// Decodes the `{XXXX}` part of a `\u{XXXX}` escape starting at byte `at` of `rest`. On failure, returns
+/// the byte offset where the malformed escape ends.
+fn unicode_escape(rest: &str, at: usize, chars: &mut Peekable<CharIndices>) -> Result<char, usize> {
+    if chars.next_if(|(_, c)| *c == '{').is_none() {
+        return Err(at + 2);
+    }
+    
+    while chars.next_if(|(_, c)| c.is_ascii_alphanumeric()).is_some() {}
+    
+    match chars.next_if(|(_, c)| *c == '}') {
+        Some((end, _)) => {
+            let digits = &rest[at + 3..end];
+            u32::from_str_radix(digits, 16)
+                .ok()
+                .filter(|_| digits.len() <= 6)
+                .and_then(char::from_u32)
+                .ok_or(end + 1)
+        }
+        None => Err(chars.peek().map_or(rest.len(), |(i, _)| *i)),
+    }
+}
+
+fn block_comment(lex: &mut logos::Lexer<Token>) -> FilterResult<(), LexError> {
+    // The delimiters are ASCII, so scanning bytes can't match inside a multi-byte character.
+    let rest = lex.remainder().as_bytes();
+    let mut depth = 1;
+    let mut i = 0;
+    
+    while i < rest.len() {
+        if rest[i..].starts_with(b"#[") {
+            depth += 1;
+            i += 2;
+        } else if rest[i..].starts_with(b"]#") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                lex.bump(i);
+                return FilterResult::Skip;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    
+    FilterResult::Error(LexError::UnterminatedComment)
+}
+
+/// Lexes `source`, dropping `##` doc comments.
+pub fn lex(source: &str) -> Result<Vec<SpannedToken>, Error> {
+    lex_with_docs(source).map(|tokens| {
+        tokens.into_iter().filter(|token| !matches!(token.node, Token::DocComment(_))).collect()
+    })
+}
+
+/// Lexes `source`, keeping `##` doc comments as `Token::DocComment` trivia for the parser to attach.
+pub fn lex_with_docs(source: &str) -> Result<Vec<SpannedToken>, Error> {
+    let mut tokens = Vec::new();
+    lex_range(source, 0..source.len(), &mut tokens)?;
+    Ok(newlines(tokens))
+}
+
+/// Keeps only the newlines that can end a statement. Newlines are insignificant inside parentheses,
+/// brackets and interpolations, after a token that needs a continuation such as a binary operator, before `then`,
+/// `else`, `.` and `|>`, and when they would repeat a separator.
+fn newlines(tokens: Vec<SpannedToken>) -> Vec<SpannedToken> {
+    let mut result: Vec<SpannedToken> = Vec::with_capacity(tokens.len());
+    let mut brackets = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    
+    while let Some(token) = tokens.next() {
+        match token.node {
+            // An interpolation's braces hold an expression, not a block, so they act like parentheses.
+            Token::LBrace if brackets.last() == Some(&Token::StringStart) => brackets.push(Token::LParen),
+            Token::LParen | Token::LBracket | Token::LBrace | Token::StringStart => brackets.push(token.node.clone()),
+            Token::RParen | Token::RBracket | Token::RBrace | Token::StringEnd => {
+                brackets.pop();
+            }
+            Token::Newline => {
+                let nested = !matches!(brackets.last(), None | Some(Token::LBrace));
+                // Doc comments are trivia: one ending a line leaves the line's last real token to decide.
+                let mut previous = result.iter().rev().filter(|token| !matches!(token.node, Token::DocComment(_)));
+                let continued = previous.next().is_none_or(|previous| continues(&previous.node));
+                let joined = tokens.peek().is_none_or(|next| matches!(next.node, Token::Then | Token::Else | Token::Dot | Token::Pipeline));
+                if nested || continued || joined {
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        result.push(token);
+    }
+    
+    result
+}
+
+/// Whether a line ending in `token` must continue on the next line.
+fn continues(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Newline | Token::Semicolon
+            | Token::If | Token::Then | Token::Else | Token::And | Token::Or | Token::Not | Token::While | Token::For | Token::Match
+            | Token::Assign | Token::Reassign | Token::Colon | Token::Comma | Token::Pipe | Token::Pipeline | Token::LParen | Token::LBracket | Token::LBrace | Token::Dot | Token::DotDot | Token::DotDotEq | Token::By
+            | Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::Percent
+            | Token::Equality | Token::NotEqual | Token::LessThan | Token::GreaterThan | Token::LessThanEqual | Token::GreaterThanEqual
+            | Token::Operator(_) | Token::Infix
+    )
+}
+
+/// Lexes `source[range]` into `tokens` with spans relative to the whole of `source`, expanding
+/// interpolated strings into `StringStart ... StringEnd` sequences.
+fn lex_range(source: &str, range: Span, tokens: &mut Vec<SpannedToken>) -> Result<(), Error> {
+    let offset = range.start;
+    let shift = |span: Span| span.start + offset..span.end + offset;
+    
+    for (token, span) in Token::lexer(&source[range]).spanned() {
+        let span = shift(span);
+        match token {
+            Ok(Token::Template(parts)) => match parts.as_slice() {
+                [TemplatePart::Text(value, _)] => tokens.push(SpannedToken::new(Token::StringLiteral(value.clone()), span)),
+                _ => {
+                    let mut end = span.start;
+                    for part in parts {
+                        match part {
+                            TemplatePart::Text(value, text) => {
+                                let text = shift(text);
+                                if end == span.start {
+                                    tokens.push(SpannedToken::new(Token::StringStart, span.start..text.start));
+                                }
+                                if !value.is_empty() {
+                                    tokens.push(SpannedToken::new(Token::StringLiteral(value), text.clone()));
+                                }
+                                end = text.end;
+                            }
+                            TemplatePart::Interpolation { expr, spec, close } => {
+                                let expr = shift(expr);
+                                tokens.push(SpannedToken::new(Token::LBrace, expr.start - 1..expr.start));
+                                lex_range(source, expr, tokens)?;
+                                if let Some((spec, spec_span)) = spec {
+                                    tokens.push(SpannedToken::new(Token::FormatSpec(spec), shift(spec_span)));
+                                }
+                                tokens.push(SpannedToken::new(Token::RBrace, close + offset..close + offset + 1));
+                            }
+                        }
+                    }
+                    tokens.push(SpannedToken::new(Token::StringEnd, end..span.end));
+                }
+            },
+            Ok(t) => tokens.push(SpannedToken::new(t, span)),
+            Err(LexError::UnexpectedCharacter) => return Err(Error::lex(format!("Unexpected character `{}`", &source[span.clone()]), span)),
+            Err(LexError::UnterminatedComment) => return Err(Error::lex("Unterminated block comment", span.start..span.start + 2)),
+            Err(LexError::UnterminatedString) => return Err(Error::lex("Unterminated string", span)),
+            Err(LexError::IntegerOverflow) => return Err(Error::lex(format!("Integer literal `{}` is out of range", &source[span.clone()]), span)),
+            #[cfg(feature = "decimal")]
+            Err(LexError::DecimalOverflow) => return Err(Error::lex(format!("Decimal literal `{}` is out of range", &source[span.clone()]), span)),
+            Err(LexError::InvalidEscape(escape)) => {
+                let escape = shift(escape);
+                return Err(Error::lex(format!("Invalid escape sequence `{}`", &source[escape.clone()]), escape));
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(source: &str) -> Vec<Token> {
+        lex(source).unwrap().into_iter().map(|token| token.node).collect()
+    }
+
+    fn with_docs(source: &str) -> Vec<Token> {
+        lex_with_docs(source).unwrap().into_iter().map(|token| token.node).collect()
+    }
+
+    fn error(source: &str) -> Error {
+        lex(source).unwrap_err()
+    }
+
+    #[test]
+    fn line_comments() {
+        assert_eq!(tokens("1 # one\n2"), vec![Token::Integer(1), Token::Newline, Token::Integer(2)]);
+        assert_eq!(tokens("#"), vec![]);
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        assert_eq!(tokens("1 #[ a #[ b ]# c ]# 2"), vec![Token::Integer(1), Token::Integer(2)]);
+        assert_eq!(tokens("#[ café ]# 1"), vec![Token::Integer(1)]);
+        assert_eq!(error("#[ #[ ]#"), Error::lex("Unterminated block comment", 0..2));
+    }
+
+    #[test]
+    fn doc_comments_are_kept_as_trivia() {
+        let expected = vec![Token::DocComment("Adds.".into()), Token::DocComment("Twice.".into()), Token::Identifier("f".into())];
+        assert_eq!(with_docs("## Adds.\n## Twice.\nf"), expected);
+        assert_eq!(tokens("## Adds.\nf"), vec![Token::Identifier("f".into())]);
+    }
+
+    #[test]
+    fn trailing_doc_comment_keeps_the_newline() {
+        assert_eq!(with_docs("1 ## one\n2"), vec![Token::Integer(1), Token::DocComment("one".into()), Token::Newline, Token::Integer(2)]);
+    }
+
+    fn string(source: &str) -> String {
+        match tokens(source).as_slice() {
+            [Token::StringLiteral(value)] => value.clone(),
+            tokens => panic!("expected one string literal, got {:?}", tokens),
+        }
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(string(r#""a\nb\t\\\"\'\{\}\0""#), "a\nb\t\\\"'{}\0");
+        assert_eq!(string(r"'\u{1F600}\u{e9}'"), "\u{1F600}\u{e9}");
+    }
+
+    #[test]
+    fn invalid_escapes_point_at_the_escape() {
+        assert_eq!(error(r#"x := "ab\q""#), Error::lex(r"Invalid escape sequence `\q`", 8..10));
+        assert_eq!(error(r#""\u{110000}""#), Error::lex(r"Invalid escape sequence `\u{110000}`", 1..11));
+        assert_eq!(error(r#""\u12""#), Error::lex(r"Invalid escape sequence `\u`", 1..3));
+    }
+
+    #[test]
+    fn raw_and_triple_quoted_strings() {
+        assert_eq!(string(r#"r"a\n{b}""#), r"a\n{b}");
+        assert_eq!(string("\"\"\"one\n\"two\" three\"\"\""), "one\n\"two\" three");
+        assert_eq!(string(r"r'''a\n'b'''"), r"a\n'b");
+        assert_eq!(error("\"one\ntwo\""), Error::lex("Unterminated string", 0..1));
+    }
+
+    fn spans(source: &str) -> Vec<(Token, Span)> {
+        lex(source).unwrap().into_iter().map(|token| (token.node, token.span)).collect()
+    }
+
+    #[test]
+    fn interpolation_spans() {
+        let expected = vec![
+            (Token::StringStart, 0..1),
+            (Token::StringLiteral("a".into()), 1..2),
+            (Token::LBrace, 2..3),
+            (Token::Identifier("x".into()), 3..4),
+            (Token::FormatSpec(">4".into()), 4..7),
+            (Token::RBrace, 7..8),
+            (Token::StringLiteral("b".into()), 8..9),
+            (Token::StringEnd, 9..10),
+        ];
+        assert_eq!(spans("\"a{x:>4}b\""), expected);
+    }
+
+    #[test]
+    fn nested_interpolation_spans() {
+        let expected = vec![
+            (Token::StringStart, 0..1),
+            (Token::LBrace, 1..2),
+            (Token::Identifier("f".into()), 2..3),
+            (Token::LParen, 3..4),
+            (Token::StringStart, 4..5),
+            (Token::LBrace, 5..6),
+            (Token::Identifier("y".into()), 6..7),
+            (Token::RBrace, 7..8),
+            (Token::StringEnd, 8..9),
+            (Token::RParen, 9..10),
+            (Token::RBrace, 10..11),
+            (Token::StringLiteral("!".into()), 11..12),
+            (Token::StringEnd, 12..13),
+        ];
+        assert_eq!(spans("\"{f(\"{y}\")}!\""), expected);
+    }
+
+    #[test]
+    fn numeric_literals() {
+        assert_eq!(tokens("0x_ff 0o17 0b1010_1010 1_000"), vec![Token::Integer(255), Token::Integer(15), Token::Integer(170), Token::Integer(1000)]);
+        assert_eq!(tokens("1e3 2.5e-1_0 0.5"), vec![Token::Number(1000.0), Token::Number(2.5e-10), Token::Number(0.5)]);
+        assert_eq!(tokens("1..2"), vec![Token::Integer(1), Token::DotDot, Token::Integer(2)]);
+    }
+
+    #[test]
+    fn integer_overflow() {
+        assert_eq!(tokens("9223372036854775807"), vec![Token::Integer(i64::MAX)]);
+        assert_eq!(error("x := 9223372036854775808"), Error::lex("Integer literal `9223372036854775808` is out of range", 5..24));
+        assert_eq!(error("0xffffffffffffffff"), Error::lex("Integer literal `0xffffffffffffffff` is out of range", 0..18));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint_literals() {
+        let big = |n: &str| Token::BigInt(n.parse().unwrap());
+        assert_eq!(tokens("123n 1_000n 99999999999999999999n"), vec![big("123"), big("1000"), big("99999999999999999999")]);
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn decimal_literals() {
+        let decimal = |n: &str| Token::Decimal(n.parse().unwrap());
+        assert_eq!(tokens("1.10d 2d 1_000.5d"), vec![decimal("1.10"), decimal("2"), decimal("1000.5")]);
+        assert_eq!(error("79228162514264337593543950336d"), Error::lex("Decimal literal `79228162514264337593543950336d` is out of range", 0..30));
     }
 }
\ No newline at end of file